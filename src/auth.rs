@@ -0,0 +1,245 @@
+use anyhow::{anyhow, Result};
+use clap::ArgEnum;
+use md5::Md5;
+use reqwest::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use reqwest::{Client, Request, RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// 认证方式
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum AuthType {
+    Basic,
+    Digest,
+    Bearer,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Auth {
+    Basic { username: String, password: String },
+    Digest { username: String, password: String },
+    Bearer { token: String },
+}
+
+impl Auth {
+    /// 解析 `-a` 的参数。basic / digest 为 `user[:password]`，省略密码时在终端提示输入；
+    /// bearer 为 token
+    pub fn new(credentials: &str, auth_type: AuthType) -> Result<Self> {
+        if auth_type == AuthType::Bearer {
+            return Ok(Auth::Bearer { token: credentials.into() });
+        }
+
+        let (username, password) = match credentials.split_once(':') {
+            Some((username, password)) => (username.to_string(), password.to_string()),
+            None => {
+                let prompt = format!("http: password for {}: ", credentials);
+                let password = rpassword::prompt_password(prompt)
+                    .map_err(|e| anyhow!("Failed to read password for {}: {}", credentials, e))?;
+                (credentials.to_string(), password)
+            }
+        };
+
+        Ok(match auth_type {
+            AuthType::Digest => Auth::Digest { username, password },
+            _ => Auth::Basic { username, password },
+        })
+    }
+
+    /// basic 和 bearer 认证可以直接加到请求上，digest 认证需要先拿到服务器的 challenge
+    pub fn apply(&self, req: RequestBuilder) -> RequestBuilder {
+        match self {
+            Auth::Basic { username, password } => req.basic_auth(username, Some(password)),
+            Auth::Bearer { token } => req.bearer_auth(token),
+            Auth::Digest { .. } => req,
+        }
+    }
+}
+
+/// 发送请求。digest 认证时如果服务器返回 401 和 digest challenge，则计算 Authorization 后重新发送。
+/// 无法复制的请求（如流式上传的文件）通过 `rebuild` 重新构造
+pub async fn send<F, Fut>(client: &Client, req: Request, auth: Option<&Auth>, rebuild: F) -> Result<Response>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<Request>>,
+{
+    let (username, password) = match auth {
+        Some(Auth::Digest { username, password }) => (username, password),
+        _ => return Ok(client.execute(req).await?),
+    };
+
+    let retry = req.try_clone();
+    let resp = client.execute(req).await?;
+    if resp.status() != StatusCode::UNAUTHORIZED {
+        return Ok(resp);
+    }
+
+    let challenge = match resp
+        .headers()
+        .get_all(WWW_AUTHENTICATE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .find_map(DigestChallenge::parse)
+    {
+        Some(v) => v,
+        None => return Ok(resp),
+    };
+
+    let mut req = match retry {
+        Some(req) => req,
+        None => rebuild().await?,
+    };
+    let uri = match req.url().query() {
+        Some(query) => format!("{}?{}", req.url().path(), query),
+        None => req.url().path().to_string(),
+    };
+    let authorization = challenge.authorization(username, password, req.method().as_str(), &uri, &cnonce())?;
+    req.headers_mut().insert(AUTHORIZATION, authorization.parse()?);
+
+    Ok(client.execute(req).await?)
+}
+
+// WWW-Authenticate 中的 digest challenge
+#[derive(Debug, Default, PartialEq)]
+struct DigestChallenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    algorithm: Option<String>,
+    qop: Option<String>,
+}
+
+impl DigestChallenge {
+    fn parse(header: &str) -> Option<Self> {
+        let header = header.trim_start();
+        let (scheme, params) = header.split_once(' ').unwrap_or((header, ""));
+        if !scheme.eq_ignore_ascii_case("digest") {
+            return None;
+        }
+
+        let mut challenge = DigestChallenge::default();
+        for (k, v) in parse_params(params) {
+            match k.to_ascii_lowercase().as_str() {
+                "realm" => challenge.realm = v,
+                "nonce" => challenge.nonce = v,
+                "opaque" => challenge.opaque = Some(v),
+                "algorithm" => challenge.algorithm = Some(v),
+                "qop" => challenge.qop = Some(v),
+                _ => {}
+            }
+        }
+
+        Some(challenge)
+    }
+
+    // 按照 RFC 7616 计算 Authorization 请求头，目前只支持 qop=auth
+    fn authorization(&self, username: &str, password: &str, method: &str, uri: &str, cnonce: &str) -> Result<String> {
+        let algorithm = self.algorithm.as_deref().unwrap_or("MD5");
+        let hash: fn(&str) -> String = match algorithm.to_ascii_uppercase().trim_end_matches("-SESS") {
+            "MD5" => |s| format!("{:x}", Md5::digest(s.as_bytes())),
+            "SHA-256" => |s| format!("{:x}", Sha256::digest(s.as_bytes())),
+            _ => return Err(anyhow!("Unsupported digest algorithm {}", algorithm)),
+        };
+
+        let mut ha1 = hash(&format!("{}:{}:{}", username, self.realm, password));
+        if algorithm.to_ascii_uppercase().ends_with("-SESS") {
+            ha1 = hash(&format!("{}:{}:{}", ha1, self.nonce, cnonce));
+        }
+        let ha2 = hash(&format!("{}:{}", method, uri));
+
+        let qop = match &self.qop {
+            Some(qop) if qop.split(',').any(|v| v.trim() == "auth") => Some("auth"),
+            Some(qop) => return Err(anyhow!("Unsupported digest qop {}", qop)),
+            None => None,
+        };
+        let nc = "00000001";
+
+        let response = match qop {
+            Some(qop) => hash(&format!("{}:{}:{}:{}:{}:{}", ha1, self.nonce, nc, cnonce, qop, ha2)),
+            None => hash(&format!("{}:{}:{}", ha1, self.nonce, ha2)),
+        };
+
+        let mut header = format!(
+            r#"Digest username="{}", realm="{}", nonce="{}", uri="{}", algorithm={}, response="{}""#,
+            username, self.realm, self.nonce, uri, algorithm, response
+        );
+        if let Some(qop) = qop {
+            header.push_str(&format!(r#", qop={}, nc={}, cnonce="{}""#, qop, nc, cnonce));
+        }
+        if let Some(opaque) = &self.opaque {
+            header.push_str(&format!(r#", opaque="{}""#, opaque));
+        }
+
+        Ok(header)
+    }
+}
+
+// 解析 `k1="v1", k2=v2` 形式的参数，引号内的值可以包含逗号
+fn parse_params(s: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut rest = s.trim();
+
+    while let Some((key, after)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim().to_string();
+        let after = after.trim_start();
+        let (value, remain) = if let Some(quoted) = after.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            (&quoted[..end], quoted.get(end + 1..).unwrap_or(""))
+        } else {
+            let end = after.find(',').unwrap_or(after.len());
+            (after[..end].trim(), &after[end..])
+        };
+        params.push((key, value.to_string()));
+        rest = remain.trim_start().trim_start_matches(',');
+    }
+
+    params
+}
+
+// 客户端随机数，不要求密码学强度
+fn cnonce() -> String {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    format!("{:x}", Md5::digest(format!("{}:{}", nanos, std::process::id()).as_bytes()))[..16].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auth_new_works() {
+        assert_eq!(
+            Auth::new("user:pa:ss", AuthType::Basic).ok(),
+            Some(Auth::Basic { username: "user".into(), password: "pa:ss".into() })
+        );
+        assert_eq!(
+            Auth::new("user:", AuthType::Digest).ok(),
+            Some(Auth::Digest { username: "user".into(), password: "".into() })
+        );
+        assert_eq!(Auth::new("token", AuthType::Bearer).ok(), Some(Auth::Bearer { token: "token".into() }));
+    }
+
+    #[test]
+    fn digest_authorization_works() {
+        // RFC 2617 3.5 中的例子
+        let challenge = DigestChallenge::parse(
+            r#"Digest realm="testrealm@host.com", qop="auth,auth-int", nonce="dcd98b7102dd2f0e8b11d0f600bfb0c093", opaque="5ccc069c403ebaf9f0171e9517f40e41""#,
+        )
+        .unwrap();
+        assert_eq!(challenge.realm, "testrealm@host.com");
+        assert_eq!(challenge.qop.as_deref(), Some("auth,auth-int"));
+
+        let header = challenge
+            .authorization("Mufasa", "Circle Of Life", "GET", "/dir/index.html", "0a4f113b")
+            .unwrap();
+        assert!(header.contains(r#"response="6629fae49393a05397450978507c4ef1""#));
+        assert!(header.contains(r#"opaque="5ccc069c403ebaf9f0171e9517f40e41""#));
+        assert!(header.contains("qop=auth, nc=00000001"));
+
+        assert!(DigestChallenge::parse(r#"Basic realm="x""#).is_none());
+        let challenge = DigestChallenge::parse(r#"Digest realm="x", nonce="n", algorithm=SHA-512"#).unwrap();
+        assert!(challenge.authorization("u", "p", "GET", "/", "c").is_err());
+    }
+}
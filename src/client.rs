@@ -0,0 +1,59 @@
+use anyhow::{anyhow, Result};
+use clap::Clap;
+use reqwest::redirect::Policy;
+use reqwest::Client;
+use std::time::Duration;
+
+use crate::proxy::ProxyArgs;
+use crate::tls::TlsArgs;
+
+// 创建 HTTP 客户端相关的参数
+
+#[derive(Clap, Debug)]
+pub struct ClientArgs {
+    /// 整个请求（包括读取响应 body）的超时时间，单位为秒，可以是小数。超时时以 2 退出
+    #[clap(long, value_name = "SECONDS", parse(try_from_str = parse_seconds))]
+    timeout: Option<Duration>,
+    /// 建立连接的超时时间，单位为秒
+    #[clap(long, value_name = "SECONDS", parse(try_from_str = parse_seconds))]
+    connect_timeout: Option<Duration>,
+    #[clap(flatten)]
+    tls: TlsArgs,
+    #[clap(flatten)]
+    proxy: ProxyArgs,
+}
+
+impl ClientArgs {
+    pub fn build(&self) -> Result<Client> {
+        // 重定向在 send 中处理
+        let mut builder = Client::builder().redirect(Policy::none());
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+
+        let builder = self.proxy.apply(builder)?;
+        Ok(self.tls.apply(builder)?.build()?)
+    }
+}
+
+fn parse_seconds(s: &str) -> Result<Duration> {
+    let secs: f64 = s.parse().map_err(|_| anyhow!("Invalid number of seconds {}", s))?;
+    Duration::try_from_secs_f64(secs).map_err(|_| anyhow!("Invalid number of seconds {}", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_seconds_works() {
+        assert_eq!(parse_seconds("3").ok(), Some(Duration::from_secs(3)));
+        assert_eq!(parse_seconds("0.5").ok(), Some(Duration::from_millis(500)));
+        assert!(parse_seconds("-1").is_err());
+        assert!(parse_seconds("abc").is_err());
+        assert!(parse_seconds("inf").is_err());
+    }
+}
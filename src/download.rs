@@ -0,0 +1,270 @@
+use anyhow::{anyhow, Context, Result};
+use clap::Clap;
+use reqwest::header::{HeaderValue, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE, RANGE};
+use reqwest::{RequestBuilder, Response, StatusCode, Url};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+
+// 下载相关的参数
+
+#[derive(Clap, Debug)]
+pub struct DownloadArgs {
+    /// 把响应 body 下载到文件，而不是输出到终端
+    #[clap(short, long)]
+    download: bool,
+    /// 下载保存的文件名，默认根据 Content-Disposition 或 URL 推断
+    #[clap(short, long, value_name = "FILE", requires = "download")]
+    output: Option<PathBuf>,
+    /// 继续下载未完成的文件，需要同时指定 --output
+    #[clap(short = 'c', long = "continue", requires_all = &["download", "output"])]
+    resume: bool,
+}
+
+/// 一次下载：保存的位置，以及续传时已经下载的字节数
+#[derive(Debug)]
+pub struct Download {
+    output: Option<PathBuf>,
+    offset: u64,
+}
+
+impl Download {
+    pub fn new(args: &DownloadArgs) -> Result<Option<Self>> {
+        if !args.download {
+            return Ok(None);
+        }
+
+        let offset = match &args.output {
+            Some(path) if args.resume => std::fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+            _ => 0,
+        };
+
+        Ok(Some(Self {
+            output: args.output.clone(),
+            offset,
+        }))
+    }
+
+    /// 续传时请求剩余的部分
+    pub fn apply(&self, req: RequestBuilder) -> RequestBuilder {
+        if self.offset > 0 {
+            req.header(RANGE, format!("bytes={}-", self.offset))
+        } else {
+            req
+        }
+    }
+
+    /// 把响应 body 以流的方式写入文件，同时在 stderr 上显示进度
+    pub async fn save(self, mut resp: Response) -> Result<()> {
+        let status = resp.status();
+        if self.offset > 0 && status == StatusCode::RANGE_NOT_SATISFIABLE {
+            eprintln!("File is already fully downloaded");
+            return Ok(());
+        }
+        if !status.is_success() {
+            return Err(anyhow!("Download failed: {}", status));
+        }
+
+        let mut offset = 0;
+        if self.offset > 0 {
+            match status {
+                StatusCode::PARTIAL_CONTENT => {
+                    let start = content_range_start(resp.headers().get(CONTENT_RANGE))
+                        .ok_or_else(|| anyhow!("Invalid Content-Range in the 206 response"))?;
+                    if start != self.offset {
+                        return Err(anyhow!(
+                            "Content-Range starts at {}, but {} bytes were already downloaded",
+                            start,
+                            self.offset
+                        ));
+                    }
+                    offset = start;
+                }
+                // 服务器不支持 Range，只能从头开始下载
+                _ => eprintln!("Server does not support resuming, downloading from the beginning"),
+            }
+        }
+
+        let path = match self.output {
+            Some(path) => path,
+            None => unique_path(&filename(&resp)),
+        };
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(offset > 0)
+            .truncate(offset == 0)
+            .open(&path)
+            .await
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+
+        let total = resp
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok()?.parse::<u64>().ok())
+            .map(|len| len + offset);
+
+        eprintln!("Downloading to {}", path.display());
+        let mut progress = Progress::new(offset, total);
+        while let Some(chunk) = resp.chunk().await? {
+            file.write_all(&chunk).await?;
+            progress.advance(chunk.len() as u64);
+        }
+        file.flush().await?;
+        progress.finish();
+
+        if let Some(total) = total {
+            if progress.done != total {
+                return Err(anyhow!("Incomplete download: {} of {} bytes", progress.done, total));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Content-Range: bytes 100-199/200 中的起始位置
+fn content_range_start(v: Option<&HeaderValue>) -> Option<u64> {
+    let range = v?.to_str().ok()?.strip_prefix("bytes ")?;
+    range.split('-').next()?.trim().parse().ok()
+}
+
+// 优先使用 Content-Disposition 中的文件名，否则使用 URL 路径的最后一段
+fn filename(resp: &Response) -> String {
+    let from_header = resp
+        .headers()
+        .get(CONTENT_DISPOSITION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(disposition_filename);
+
+    from_header
+        .or_else(|| url_filename(resp.url()))
+        .unwrap_or_else(|| "index".into())
+}
+
+fn disposition_filename(v: &str) -> Option<String> {
+    let name = v.split(';').find_map(|part| {
+        let (k, v) = part.split_once('=')?;
+        k.trim().eq_ignore_ascii_case("filename").then(|| v.trim().trim_matches('"').to_string())
+    })?;
+
+    // 只保留文件名部分，避免写到其它目录
+    let name = Path::new(&name).file_name()?.to_string_lossy().into_owned();
+    (!name.is_empty()).then_some(name)
+}
+
+fn url_filename(url: &Url) -> Option<String> {
+    let name = url.path_segments()?.next_back()?;
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+// 文件已经存在时，依次尝试 name-1、name-2 ……
+fn unique_path(name: &str) -> PathBuf {
+    let mut path = PathBuf::from(name);
+    let mut i = 1;
+    while path.exists() {
+        path = PathBuf::from(format!("{}-{}", name, i));
+        i += 1;
+    }
+    path
+}
+
+// stderr 上的下载进度条。stderr 不是终端时只在结束时输出一行
+struct Progress {
+    done: u64,
+    total: Option<u64>,
+    start: Instant,
+    last_draw: Option<Instant>,
+    tty: bool,
+}
+
+impl Progress {
+    const WIDTH: u64 = 30;
+
+    fn new(done: u64, total: Option<u64>) -> Self {
+        Self {
+            done,
+            total,
+            start: Instant::now(),
+            last_draw: None,
+            tty: atty::is(atty::Stream::Stderr),
+        }
+    }
+
+    fn advance(&mut self, n: u64) {
+        self.done += n;
+        let due = self.last_draw.is_none_or(|t| t.elapsed() >= Duration::from_millis(100));
+        if self.tty && due {
+            self.draw();
+            self.last_draw = Some(Instant::now());
+        }
+    }
+
+    fn finish(&mut self) {
+        if self.tty {
+            self.draw();
+            eprintln!();
+        }
+        eprintln!("Done. {} in {:.2}s", human_size(self.done), self.start.elapsed().as_secs_f64());
+    }
+
+    fn draw(&self) {
+        let line = match self.total {
+            Some(total) if total > 0 => {
+                let filled = (self.done.min(total) * Self::WIDTH / total) as usize;
+                format!(
+                    "[{}{}] {:>3}% {} / {}",
+                    "#".repeat(filled),
+                    " ".repeat(Self::WIDTH as usize - filled),
+                    self.done.min(total) * 100 / total,
+                    human_size(self.done),
+                    human_size(total)
+                )
+            }
+            _ => human_size(self.done),
+        };
+        eprint!("\r{}", line);
+        let _ = std::io::stderr().flush();
+    }
+}
+
+fn human_size(n: u64) -> String {
+    const UNITS: &[&str] = &["B", "kB", "MB", "GB", "TB"];
+    let mut size = n as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", n, UNITS[0])
+    } else {
+        format!("{:.2} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filename_works() {
+        assert_eq!(disposition_filename(r#"attachment; filename="a.zip""#), Some("a.zip".into()));
+        assert_eq!(disposition_filename("attachment; FileName=b.tar.gz"), Some("b.tar.gz".into()));
+        assert_eq!(disposition_filename(r#"attachment; filename="../../etc/passwd""#), Some("passwd".into()));
+        assert_eq!(disposition_filename("inline"), None);
+        assert_eq!(url_filename(&"http://a.com/x/y.bin?z=1".parse().unwrap()), Some("y.bin".into()));
+        assert_eq!(url_filename(&"http://a.com/".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn content_range_start_works() {
+        assert_eq!(content_range_start(Some(&HeaderValue::from_static("bytes 100-199/200"))), Some(100));
+        assert_eq!(content_range_start(Some(&HeaderValue::from_static("bytes */200"))), None);
+        assert_eq!(content_range_start(None), None);
+        assert_eq!(human_size(999), "999 B");
+        assert_eq!(human_size(1_500_000), "1.50 MB");
+    }
+}
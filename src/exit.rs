@@ -0,0 +1,81 @@
+use reqwest::StatusCode;
+
+use crate::redirect::TooManyRedirects;
+
+// 进程的退出码，和 HTTPie 保持一致，连接错误额外使用 7
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExitStatus {
+    Success = 0,
+    Error = 1,
+    Timeout = 2,
+    Http3xx = 3,
+    Http4xx = 4,
+    Http5xx = 5,
+    TooManyRedirects = 6,
+    ConnectionError = 7,
+}
+
+impl ExitStatus {
+    /// --check-status 时按响应的状态码决定退出码
+    pub fn from_status(status: StatusCode) -> Self {
+        if status.is_redirection() {
+            ExitStatus::Http3xx
+        } else if status.is_client_error() {
+            ExitStatus::Http4xx
+        } else if status.is_server_error() {
+            ExitStatus::Http5xx
+        } else {
+            ExitStatus::Success
+        }
+    }
+
+    /// 根据错误链中的 reqwest 错误区分超时、连接错误和重定向次数过多，其它错误为 1
+    pub fn from_error(err: &anyhow::Error) -> Self {
+        if err.is::<TooManyRedirects>() {
+            return ExitStatus::TooManyRedirects;
+        }
+
+        let err = match err.chain().find_map(|e| e.downcast_ref::<reqwest::Error>()) {
+            Some(e) => e,
+            None => return ExitStatus::Error,
+        };
+
+        if err.is_timeout() {
+            ExitStatus::Timeout
+        } else if err.is_redirect() {
+            ExitStatus::TooManyRedirects
+        } else if err.is_connect() {
+            ExitStatus::ConnectionError
+        } else {
+            ExitStatus::Error
+        }
+    }
+
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_status_works() {
+        assert_eq!(ExitStatus::from_status(StatusCode::OK), ExitStatus::Success);
+        assert_eq!(ExitStatus::from_status(StatusCode::MOVED_PERMANENTLY), ExitStatus::Http3xx);
+        assert_eq!(ExitStatus::from_status(StatusCode::NOT_FOUND), ExitStatus::Http4xx);
+        assert_eq!(ExitStatus::from_status(StatusCode::SERVICE_UNAVAILABLE), ExitStatus::Http5xx);
+    }
+
+    #[tokio::test]
+    async fn from_error_works() {
+        assert_eq!(ExitStatus::from_error(&anyhow::anyhow!("oops")), ExitStatus::Error);
+        assert_eq!(ExitStatus::from_error(&TooManyRedirects(3).into()), ExitStatus::TooManyRedirects);
+
+        // 端口 1 上通常没有服务
+        let err = reqwest::get("http://127.0.0.1:1/").await.unwrap_err();
+        assert_eq!(ExitStatus::from_error(&err.into()), ExitStatus::ConnectionError);
+    }
+}
@@ -0,0 +1,172 @@
+use clap::ArgEnum;
+use mime::Mime;
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+use syntect::util::{as_24_bit_terminal_escaped, LinesWithEndings};
+
+// --style 可选的配色，对应 syntect 自带的主题
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    OceanDark,
+    OceanLight,
+    EightiesDark,
+    MochaDark,
+    Github,
+    SolarizedDark,
+    SolarizedLight,
+}
+
+impl Style {
+    fn theme_name(self) -> &'static str {
+        match self {
+            Style::OceanDark => "base16-ocean.dark",
+            Style::OceanLight => "base16-ocean.light",
+            Style::EightiesDark => "base16-eighties.dark",
+            Style::MochaDark => "base16-mocha.dark",
+            Style::Github => "InspiredGitHub",
+            Style::SolarizedDark => "Solarized (dark)",
+            Style::SolarizedLight => "Solarized (light)",
+        }
+    }
+}
+
+/// 支持高亮的 body 类型
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Syntax {
+    Json,
+    Xml,
+    Html,
+    Yaml,
+    JavaScript,
+    Css,
+}
+
+impl Syntax {
+    pub fn from_mime(m: &Mime) -> Option<Self> {
+        // application/problem+json、application/atom+xml 等按后缀识别
+        match m.suffix().map(|v| v.as_str()) {
+            Some("json") => return Some(Syntax::Json),
+            Some("xml") => return Some(Syntax::Xml),
+            Some("yaml") => return Some(Syntax::Yaml),
+            _ => {}
+        }
+
+        let syntax = match (m.type_().as_str(), m.subtype().as_str()) {
+            ("application", "json") | ("text", "json") => Syntax::Json,
+            ("application", "xml") | ("text", "xml") => Syntax::Xml,
+            ("text", "html") | ("application", "xhtml") => Syntax::Html,
+            ("application", "yaml") | ("application", "x-yaml") | ("text", "yaml") | ("text", "x-yaml") => Syntax::Yaml,
+            ("application", "javascript") | ("text", "javascript") | ("application", "ecmascript") => {
+                Syntax::JavaScript
+            }
+            ("text", "css") => Syntax::Css,
+            _ => return None,
+        };
+
+        Some(syntax)
+    }
+
+    /// 没有 Content-Type 或者为 text/plain 时，根据内容猜测是否为 JSON
+    pub fn sniff(body: &str) -> Option<Self> {
+        let body = body.trim_start();
+        if !(body.starts_with('{') || body.starts_with('[')) {
+            return None;
+        }
+
+        serde_json::from_str::<serde::de::IgnoredAny>(body).ok().map(|_| Syntax::Json)
+    }
+
+    // syntect 按扩展名查找语法定义
+    fn extension(self) -> &'static str {
+        match self {
+            Syntax::Json => "json",
+            Syntax::Xml => "xml",
+            Syntax::Html => "html",
+            Syntax::Yaml => "yaml",
+            Syntax::JavaScript => "js",
+            Syntax::Css => "css",
+        }
+    }
+}
+
+// 语法和主题的加载比较慢，只在第一次高亮时加载一次
+static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+
+/// 按语法把文本转换为带 24 位色 ANSI 转义的字符串
+pub fn highlight(text: &str, syntax: Syntax, style: Style) -> String {
+    let syntaxes = SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines);
+    let themes = THEMES.get_or_init(ThemeSet::load_defaults);
+    let (syntax, theme) = match (syntaxes.find_syntax_by_extension(syntax.extension()), themes.themes.get(style.theme_name())) {
+        (Some(syntax), Some(theme)) => (syntax, theme),
+        _ => return text.to_string(),
+    };
+
+    let mut h = HighlightLines::new(syntax, theme);
+    let mut out = String::with_capacity(text.len() * 2);
+    for line in LinesWithEndings::from(text) {
+        match h.highlight_line(line, syntaxes) {
+            Ok(ranges) => out.push_str(&as_24_bit_terminal_escaped(&ranges, false)),
+            Err(_) => out.push_str(line),
+        }
+    }
+    out.push_str("\x1b[0m");
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn syntax_from_mime_works() {
+        let syntax = |s: &str| Syntax::from_mime(&s.parse().unwrap());
+        assert_eq!(syntax("application/json"), Some(Syntax::Json));
+        assert_eq!(syntax("text/html; charset=utf-8"), Some(Syntax::Html));
+        assert_eq!(syntax("application/xml"), Some(Syntax::Xml));
+        assert_eq!(syntax("application/x-yaml"), Some(Syntax::Yaml));
+        assert_eq!(syntax("text/javascript"), Some(Syntax::JavaScript));
+        assert_eq!(syntax("text/css"), Some(Syntax::Css));
+        assert_eq!(syntax("text/plain"), None);
+        assert_eq!(syntax("application/json; charset=utf-8"), Some(Syntax::Json));
+        assert_eq!(syntax("application/problem+json"), Some(Syntax::Json));
+        assert_eq!(syntax("application/vnd.api+json"), Some(Syntax::Json));
+        assert_eq!(syntax("text/json"), Some(Syntax::Json));
+        assert_eq!(syntax("application/atom+xml"), Some(Syntax::Xml));
+    }
+
+    #[test]
+    fn sniff_works() {
+        assert_eq!(Syntax::sniff(" {\"a\": [1, 2]}\n"), Some(Syntax::Json));
+        assert_eq!(Syntax::sniff("[]"), Some(Syntax::Json));
+        assert_eq!(Syntax::sniff("{not json}"), None);
+        assert_eq!(Syntax::sniff("hello"), None);
+        assert_eq!(Syntax::sniff("42"), None);
+    }
+
+    #[test]
+    fn highlight_works() {
+        let out = highlight("{\"a\": 1}\n", Syntax::Json, Style::OceanDark);
+        assert!(out.contains("\x1b[38;2;"));
+        // 不同的 token 使用不同的颜色
+        let colors: std::collections::HashSet<_> = out.split("\x1b[").filter(|s| s.starts_with("38;2;")).collect();
+        assert!(colors.len() > 1);
+        assert_eq!(strip_ansi(&out), "{\"a\": 1}\n");
+    }
+
+    fn strip_ansi(s: &str) -> String {
+        let mut out = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|c| *c == 'm');
+            } else {
+                out.push(c);
+            }
+        }
+        out
+    }
+}
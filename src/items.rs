@@ -0,0 +1,169 @@
+use anyhow::{anyhow, Context, Result};
+use mime::Mime;
+use reqwest::header::{HeaderName, HeaderValue};
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+// 请求项的分隔符。同一位置上较长的分隔符优先匹配，例如 `:=@` 优先于 `:=` 和 `:`
+const SEPARATORS: &[&str] = &[":=@", "=@", "==", ":=", "=", "@", ":", ";"];
+
+/// 命令行上的请求项，语法与 HTTPie 保持一致：
+///
+/// - `Header:Value`：请求头，`Header:` 或 `Header;` 发送值为空的请求头
+/// - `param==value`：URL 查询参数
+/// - `field=value`：字符串类型的数据字段
+/// - `field:=json`：原始 JSON 数据字段，如数字、布尔值、数组、对象
+/// - `field=@file`：从文件读取文本作为字符串数据字段
+/// - `field:=@file.json`：从文件读取 JSON 作为数据字段
+/// - `field@path`：multipart 上传的文件，可以用 `field@path;type=mime` 指定文件类型
+/// - `@path`：把整个文件作为请求的 body
+#[derive(Debug, PartialEq)]
+pub enum RequestItem {
+    Header(HeaderName, HeaderValue),
+    Query(String, String),
+    Data(String, String),
+    Json(String, Value),
+    File(FileItem),
+    Body(PathBuf),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct FileItem {
+    pub name: String,
+    pub path: PathBuf,
+    pub mime: Option<Mime>,
+}
+
+impl FileItem {
+    fn parse(name: &str, s: &str) -> Result<Self> {
+        let (path, mime) = match s.rsplit_once(";type=") {
+            Some((path, mime)) => (path, Some(mime.parse()?)),
+            None => (s, None),
+        };
+
+        Ok(Self {
+            name: name.into(),
+            path: path.into(),
+            mime,
+        })
+    }
+}
+
+impl FromStr for RequestItem {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, sep, value) = split_item(s).ok_or_else(|| anyhow!("Failed to parse {}", s))?;
+        if key.is_empty() && sep == "@" && !value.is_empty() {
+            return Ok(RequestItem::Body(value.into()));
+        }
+        if key.is_empty() {
+            return Err(anyhow!("Failed to parse {}: missing name", s));
+        }
+
+        let item = match sep {
+            ":" => RequestItem::Header(key.parse()?, value.parse()?),
+            ";" if value.is_empty() => RequestItem::Header(key.parse()?, HeaderValue::from_static("")),
+            ";" => return Err(anyhow!("Failed to parse {}: `Header;` cannot have a value", s)),
+            "==" => RequestItem::Query(key.into(), value.into()),
+            "=" => RequestItem::Data(key.into(), value.into()),
+            ":=" => RequestItem::Json(key.into(), parse_json(value)?),
+            "=@" => RequestItem::Data(key.into(), read_file(value)?),
+            ":=@" => RequestItem::Json(key.into(), parse_json(&read_file(value)?)?),
+            "@" => RequestItem::File(FileItem::parse(key, value)?),
+            _ => unreachable!(),
+        };
+
+        Ok(item)
+    }
+}
+
+// 找到最靠前的分隔符，把请求项拆成 (key, 分隔符, value)
+fn split_item(s: &str) -> Option<(&str, &'static str, &str)> {
+    s.char_indices().find_map(|(i, _)| {
+        SEPARATORS
+            .iter()
+            .find(|sep| s[i..].starts_with(*sep))
+            .map(|sep| (&s[..i], *sep, &s[i + sep.len()..]))
+    })
+}
+
+fn parse_json(s: &str) -> Result<Value> {
+    serde_json::from_str(s).with_context(|| format!("Invalid JSON: {}", s))
+}
+
+fn read_file(path: &str) -> Result<String> {
+    fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))
+}
+
+pub fn parse_request_item(s: &str) -> Result<RequestItem> {
+    s.parse()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_request_item_works() {
+        assert!(parse_request_item("").is_err());
+        assert!(parse_request_item("a").is_err());
+        assert!(parse_request_item("=b").is_err());
+        assert_eq!(parse_request_item("a=").ok(), Some(RequestItem::Data("a".into(), "".into())));
+        assert_eq!(parse_request_item("a=b").ok(), Some(RequestItem::Data("a".into(), "b".into())));
+        assert_eq!(parse_request_item("a=b=c").ok(), Some(RequestItem::Data("a".into(), "b=c".into())));
+        assert_eq!(parse_request_item("a==b").ok(), Some(RequestItem::Query("a".into(), "b".into())));
+        assert_eq!(parse_request_item("a:=[1, true]").ok(), Some(RequestItem::Json("a".into(), json!([1, true]))));
+        assert!(parse_request_item("a:=nope").is_err());
+        assert_eq!(
+            parse_request_item("a@b.txt").ok(),
+            Some(RequestItem::File(FileItem { name: "a".into(), path: "b.txt".into(), mime: None }))
+        );
+        assert_eq!(
+            parse_request_item("a@b.png;type=image/png").ok(),
+            Some(RequestItem::File(FileItem { name: "a".into(), path: "b.png".into(), mime: Some(mime::IMAGE_PNG) }))
+        );
+        assert!(parse_request_item("a@b.png;type=png").is_err());
+        assert_eq!(parse_request_item("@b.json").ok(), Some(RequestItem::Body("b.json".into())));
+        assert!(parse_request_item("@").is_err());
+        assert_eq!(
+            parse_request_item("X-Token:a=b").ok(),
+            Some(RequestItem::Header(HeaderName::from_static("x-token"), HeaderValue::from_static("a=b")))
+        );
+        assert_eq!(
+            parse_request_item("X-Empty:").ok(),
+            Some(RequestItem::Header(HeaderName::from_static("x-empty"), HeaderValue::from_static("")))
+        );
+        assert_eq!(
+            parse_request_item("X-Empty;").ok(),
+            Some(RequestItem::Header(HeaderName::from_static("x-empty"), HeaderValue::from_static("")))
+        );
+        assert!(parse_request_item("X-Empty;a").is_err());
+        assert!(parse_request_item("Bad Name:a").is_err());
+    }
+
+    #[test]
+    fn parse_request_item_from_file_works() {
+        let dir = std::env::temp_dir().join(format!("httpie-items-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let text = dir.join("a.txt");
+        let json = dir.join("a.json");
+        fs::write(&text, "hello").unwrap();
+        fs::write(&json, r#"{"b": 1}"#).unwrap();
+
+        assert_eq!(
+            parse_request_item(&format!("a=@{}", text.display())).ok(),
+            Some(RequestItem::Data("a".into(), "hello".into()))
+        );
+        assert_eq!(
+            parse_request_item(&format!("a:=@{}", json.display())).ok(),
+            Some(RequestItem::Json("a".into(), json!({"b": 1})))
+        );
+        assert!(parse_request_item(&format!("a=@{}", dir.join("missing").display())).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
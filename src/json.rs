@@ -0,0 +1,156 @@
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+
+// 嵌套 JSON 的 key 路径，语法与 HTTPie 保持一致：
+// `user[name]=x` 生成 {"user": {"name": "x"}}，`tags[]=a` 生成 {"tags": ["a"]}，
+// `list[1]=b` 生成 {"list": [null, "b"]}
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+    Append,
+}
+
+/// 把若干个 (key 路径, 值) 合并成一个 JSON body
+pub fn build_body<'a, I>(fields: I) -> Result<Value>
+where
+    I: IntoIterator<Item = (&'a str, Value)>,
+{
+    let mut body = Value::Null;
+    for (key, value) in fields {
+        let path = parse_path(key)?;
+        insert(&mut body, &path, value).map_err(|e| anyhow!("Failed to set {}: {}", key, e))?;
+    }
+
+    // 没有任何字段时也保证 body 是一个对象
+    if body.is_null() {
+        body = Value::Object(Map::new());
+    }
+
+    Ok(body)
+}
+
+fn parse_path(key: &str) -> Result<Vec<Segment>> {
+    let err = || anyhow!("Invalid nested key {}", key);
+
+    let (head, mut rest) = match key.find('[') {
+        Some(i) => key.split_at(i),
+        None => (key, ""),
+    };
+
+    let mut path = Vec::new();
+    if !head.is_empty() {
+        path.push(Segment::Key(head.into()));
+    }
+
+    while !rest.is_empty() {
+        if !rest.starts_with('[') {
+            return Err(err());
+        }
+        let end = rest.find(']').ok_or_else(err)?;
+        let inner = &rest[1..end];
+        let segment = if inner.is_empty() {
+            Segment::Append
+        } else if let Ok(i) = inner.parse() {
+            Segment::Index(i)
+        } else {
+            Segment::Key(inner.into())
+        };
+        path.push(segment);
+        rest = &rest[end + 1..];
+    }
+
+    if path.is_empty() {
+        return Err(err());
+    }
+
+    Ok(path)
+}
+
+// 数组下标的上限，中间缺少的元素会补 null，过大的下标会耗尽内存
+const MAX_INDEX: usize = 10_000;
+
+fn insert(target: &mut Value, path: &[Segment], value: Value) -> Result<()> {
+    let (segment, rest) = match path.split_first() {
+        Some(v) => v,
+        None => {
+            *target = value;
+            return Ok(());
+        }
+    };
+
+    match segment {
+        Segment::Key(k) => {
+            if target.is_null() {
+                *target = Value::Object(Map::new());
+            }
+            let obj = target
+                .as_object_mut()
+                .ok_or_else(|| anyhow!("cannot use key `{}` on a non-object", k))?;
+            insert(obj.entry(k.clone()).or_insert(Value::Null), rest, value)
+        }
+        Segment::Index(i) => {
+            if *i > MAX_INDEX {
+                return Err(anyhow!("array index {} is too large, the maximum is {}", i, MAX_INDEX));
+            }
+            let arr = as_array(target)?;
+            if arr.len() <= *i {
+                arr.resize(i + 1, Value::Null);
+            }
+            insert(&mut arr[*i], rest, value)
+        }
+        Segment::Append => {
+            let arr = as_array(target)?;
+            arr.push(Value::Null);
+            let last = arr.len() - 1;
+            insert(&mut arr[last], rest, value)
+        }
+    }
+}
+
+fn as_array(target: &mut Value) -> Result<&mut Vec<Value>> {
+    if target.is_null() {
+        *target = Value::Array(Vec::new());
+    }
+    target.as_array_mut().ok_or_else(|| anyhow!("cannot use an index on a non-array"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn build_body_works() {
+        let body = build_body(vec![
+            ("count", json!(3)),
+            ("user[name]", json!("x")),
+            ("user[roles][]", json!("admin")),
+            ("user[roles][]", json!("dev")),
+            ("tags[1]", json!("b")),
+        ])
+        .unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "count": 3,
+                "user": {"name": "x", "roles": ["admin", "dev"]},
+                "tags": [null, "b"],
+            })
+        );
+
+        assert_eq!(build_body(vec![("[]", json!(1)), ("[]", json!(2))]).unwrap(), json!([1, 2]));
+        assert_eq!(build_body(vec![]).unwrap(), json!({}));
+    }
+
+    #[test]
+    fn build_body_rejects_invalid_paths() {
+        assert!(build_body(vec![("a[b", json!(1))]).is_err());
+        assert!(build_body(vec![("a[b]c", json!(1))]).is_err());
+        assert!(build_body(vec![("a", json!(1)), ("a[b]", json!(2))]).is_err());
+        assert!(build_body(vec![("a[b]", json!(1)), ("a[]", json!(2))]).is_err());
+        assert!(build_body(vec![("a[100000000000]", json!(1))]).is_err());
+        assert!(build_body(vec![("a[10001]", json!(1))]).is_err());
+        assert!(build_body(vec![("a[10000]", json!(1))]).is_ok());
+    }
+}
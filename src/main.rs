@@ -1,10 +1,37 @@
 use anyhow::{anyhow, Result};
 use clap::{AppSettings, Clap};
-use reqwest::{Url, header, Client, Response};
-use std::str::FromStr;
-use std::collections::HashMap;
-use colored::*;
-use mime::{Mime, APPLICATION_JSON};
+use reqwest::header::{HeaderName, COOKIE};
+use reqwest::{Url, Client, Method, StatusCode};
+use std::time::Instant;
+
+mod auth;
+mod client;
+mod download;
+mod exit;
+mod highlight;
+mod items;
+mod json;
+mod output;
+mod proxy;
+mod redirect;
+mod request;
+mod retry;
+mod session;
+mod snippet;
+#[cfg(test)]
+mod test_utils;
+mod tls;
+
+use auth::{Auth, AuthType};
+use client::ClientArgs;
+use download::{Download, DownloadArgs};
+use exit::ExitStatus;
+use items::{parse_request_item, RequestItem};
+use output::{Meta, OutputArgs};
+use redirect::{RedirectArgs, Snapshot, TooManyRedirects};
+use retry::RetryArgs;
+use session::{Session, SessionArgs};
+use snippet::Snippet;
 
 // 定义 HTTPie 的 CLI 的主入口，它包含若干个子命令
 // 下面 /// 的注释是文档，clap 会将其作为 CLI 的帮助
@@ -18,167 +45,372 @@ struct Opts {
     subcmd: SubCommand,
 }
 
-// 子命令分别对应不同的 HTTP 方法，目前只支持 get / post
+// 子命令分别对应不同的 HTTP 方法，所有方法共用同一套参数
 #[derive(Clap, Debug)]
 enum SubCommand {
-    Get(Get),
-    Post(Post),
-    // 我们暂且不支持其它 HTTP 方法
+    /// feed get with an url and we will retrieve the response for you
+    Get(RequestArgs),
+    /// feed post with an url and optional key=value pairs. We will post the data
+    /// as JSON, and retrieve the response for you
+    Post(RequestArgs),
+    /// feed put with an url and optional key=value pairs, sent as JSON
+    Put(RequestArgs),
+    /// feed patch with an url and optional key=value pairs, sent as JSON
+    Patch(RequestArgs),
+    /// feed delete with an url and we will delete the resource for you
+    Delete(RequestArgs),
+    /// feed head with an url and we will retrieve the response headers for you
+    Head(RequestArgs),
+    /// feed options with an url and we will retrieve the allowed methods for you
+    Options(RequestArgs),
+    /// send a request with an arbitrary HTTP method, e.g. `request PURGE <url>`
+    Request(CustomRequest),
 }
 
-// get 子命令
+// 所有子命令共用的参数。需要输入一个 URL，和若干个可选的请求项，用于提供请求头、查询参数和 body
 
-/// feed get with an url and we will retrieve the response for you
+/// an url and optional request items. If any data field is given, we will send
+/// the data as JSON, or as a form with --form / --multipart
 #[derive(Clap, Debug)]
-struct Get {
-    /// HTTP 请求的 URL
+struct RequestArgs {
+    /// HTTP 请求的 URL，可以省略 scheme，`:3000/foo` 是 `http://localhost:3000/foo` 的简写
     #[clap(parse(try_from_str = parse_url))]
     url: String,
+    /// HTTP 请求项：Header:Value、param==value、field=value、field:=json、
+    /// field=@file、field:=@file.json、field@path
+    #[clap(parse(try_from_str = parse_request_item))]
+    items: Vec<RequestItem>,
+    /// 以 application/x-www-form-urlencoded 发送数据字段，有文件时改用 multipart/form-data
+    #[clap(short, long)]
+    form: bool,
+    /// 总是以 multipart/form-data 发送数据字段和文件
+    #[clap(long, conflicts_with = "form")]
+    multipart: bool,
+    /// 原样发送的请求 body，也可以通过管道输入或 @file 提供
+    #[clap(long)]
+    raw: Option<String>,
+    /// 不从管道输入的 stdin 读取请求 body
+    #[clap(short = 'I', long)]
+    ignore_stdin: bool,
+    /// 不发送指定的默认请求头，如 User-Agent，可以多次使用。Accept 和 Host 总会发送，不能去掉
+    #[clap(
+        long,
+        value_name = "NAME",
+        multiple_occurrences = true,
+        number_of_values = 1,
+        parse(try_from_str = request::parse_removable_header)
+    )]
+    remove_header: Vec<HeaderName>,
+    /// 认证信息：basic / digest 为 user[:password]，省略密码时会提示输入；bearer 为 token
+    #[clap(short, long, value_name = "USER[:PASS] | TOKEN")]
+    auth: Option<String>,
+    /// 认证方式
+    #[clap(short = 'A', long, arg_enum, default_value = "basic")]
+    auth_type: AuthType,
+    /// URL 省略 scheme 时使用的 scheme
+    #[clap(long, value_name = "SCHEME", default_value = "http")]
+    default_scheme: String,
+    /// 响应状态码为 3xx、4xx、5xx 时分别以 3、4、5 退出
+    #[clap(long)]
+    check_status: bool,
+    /// 只构造并输出请求，不发送。默认输出请求头和请求 body
+    #[clap(long)]
+    offline: bool,
+    /// 不发送请求，而是输出等价的 curl、wget 命令或者 Rust、Python、JavaScript 代码
+    #[clap(long = "as", arg_enum, value_name = "FORMAT", conflicts_with = "offline")]
+    as_snippet: Option<snippet::Format>,
+    #[clap(flatten)]
+    output: OutputArgs,
+    #[clap(flatten)]
+    download: DownloadArgs,
+    #[clap(flatten)]
+    redirect: RedirectArgs,
+    #[clap(flatten)]
+    client: ClientArgs,
+    #[clap(flatten)]
+    retry: RetryArgs,
+    #[clap(flatten)]
+    session: SessionArgs,
 }
 
-// post 子命令。需要输入一个 URL，和若干个可选的 key=value，用于提供 json body
+// request 子命令。在 URL 之前额外需要一个 HTTP 方法
 
-/// feed post with an url and optional key=value pairs. We will post the data
-/// as JSON, and retrieve the response for you
+/// feed request with a method, an url and optional request items
 #[derive(Clap, Debug)]
-struct Post {
-    /// HTTP 请求的 URL
-    #[clap(parse(try_from_str = parse_url))]
-    url: String,
-    /// HTTP 请求的 body
-    #[clap(parse(try_from_str = parse_kv_pair))]
-    body: Vec<KvPair>,
+struct CustomRequest {
+    /// HTTP 请求的方法，如 GET、PURGE
+    #[clap(parse(try_from_str = parse_method))]
+    method: Method,
+    #[clap(flatten)]
+    args: RequestArgs,
 }
 
-fn parse_url(s: &str) -> Result<String> {
-    // 这里我们仅仅检查一下 URL 是否合法
-    let _url: Url = s.parse()?;
-    Ok(s.into())
+impl SubCommand {
+    fn method(&self) -> Method {
+        match self {
+            SubCommand::Get(_) => Method::GET,
+            SubCommand::Post(_) => Method::POST,
+            SubCommand::Put(_) => Method::PUT,
+            SubCommand::Patch(_) => Method::PATCH,
+            SubCommand::Delete(_) => Method::DELETE,
+            SubCommand::Head(_) => Method::HEAD,
+            SubCommand::Options(_) => Method::OPTIONS,
+            SubCommand::Request(req) => req.method.clone(),
+        }
+    }
+
+    fn into_args(self) -> RequestArgs {
+        match self {
+            SubCommand::Get(args)
+            | SubCommand::Post(args)
+            | SubCommand::Put(args)
+            | SubCommand::Patch(args)
+            | SubCommand::Delete(args)
+            | SubCommand::Head(args)
+            | SubCommand::Options(args) => args,
+            SubCommand::Request(req) => req.args,
+        }
+    }
 }
 
-#[derive(Debug, PartialEq)]
-struct KvPair {
-    k: String,
-    v: String,
+fn parse_url(s: &str) -> Result<String> {
+    // 这里我们仅仅检查一下 URL 是否合法，省略的 scheme 在 main 中根据 --default-scheme 补全
+    normalize_url(s, "http")?;
+    Ok(s.into())
 }
 
-impl FromStr for KvPair {
-    type Err = anyhow::Error;
+fn normalize_url(s: &str, default_scheme: &str) -> Result<String> {
+    let has_scheme = s.split_once("://").is_some_and(|(scheme, _)| {
+        !scheme.is_empty() && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+    });
+
+    let url = if has_scheme {
+        s.to_string()
+    } else if let Some(rest) = s.strip_prefix(':') {
+        // :3000/foo、:/foo 指向 localhost
+        let sep = if rest.is_empty() || rest.starts_with('/') { "" } else { ":" };
+        format!("{}://localhost{}{}", default_scheme, sep, rest)
+    } else {
+        format!("{}://{}", default_scheme, s)
+    };
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut split = s.split("=");
-        let err = || anyhow!("Failed to parse {}", s);
-        Ok(Self {
-            k: split.next().ok_or_else(err)?.to_string(),
-            v: split.next().ok_or_else(err)?.to_string(),
-        })
+    let parsed: Url = url.parse()?;
+    if !parsed.has_host() || parsed.host_str() == Some("") {
+        return Err(anyhow!("Invalid URL {}: missing host", s));
     }
-}
 
-fn parse_kv_pair(s: &str) -> Result<KvPair> {
-    Ok(s.parse()?)
+    Ok(url)
 }
 
+fn parse_method(s: &str) -> Result<Method> {
+    // 方法名不区分大小写，统一转换为大写
+    Ok(s.to_ascii_uppercase().parse()?)
+}
 
-async fn get(client: Client, args: &Get) -> Result<()> {
-    let resp = client.get(&args.url).send().await?;
 
-    print_response(resp).await?;
+// 发送请求并输出，返回最终响应的状态码，--offline 和 --as 时返回 None
+async fn send(client: Client, method: Method, args: &RequestArgs) -> Result<Option<StatusCode>> {
+    let mut session = Session::load(&args.session, &args.url)?;
 
-    Ok(())
-}
+    // 命令行上的认证信息优先于 session 中保存的
+    let auth = match (&args.auth, session.as_ref().and_then(|s| s.auth())) {
+        (Some(credentials), _) => Some(Auth::new(credentials, args.auth_type)?),
+        (None, auth) => auth.cloned(),
+    };
 
-async fn post(client: Client, args: &Post) -> Result<()> {
-    let body = {
-        let mut body = HashMap::with_capacity(args.body.len());
+    let download = Download::new(&args.download)?;
 
-        for pair in args.body.iter() {
-            body.insert(&pair.k, &pair.v);
+    let build = || async {
+        let mut req = request::build(&client, method.clone(), args).await?;
+        if let Some(auth) = &auth {
+            req = auth.apply(req);
         }
-
-        body
+        if let Some(download) = &download {
+            req = download.apply(req);
+        }
+        let mut req = req.build()?;
+        if let Some(session) = &session {
+            session.apply(&mut req);
+        }
+        Ok(req)
     };
 
-    let resp = client.post(&args.url).json(&body).send().await?;
+    let req = build().await?;
+    if let Some(format) = args.as_snippet {
+        println!("{}", Snippet::new(&req, &args.items)?.render(format)?);
+        return Ok(None);
+    }
 
-    print_response(resp).await?;
+    let printer = args.output.printer(args.offline);
+    printer.print_request(&req)?;
+    if args.offline {
+        return Ok(None);
+    }
 
-    Ok(())
-}
+    let start = Instant::now();
+    let origin = req.url().clone();
+    let mut snapshot = Snapshot::new(&req);
+    let mut resp = retry::send(&args.retry, req, |req| auth::send(&client, req, auth.as_ref(), build), build).await?;
+
+    let mut redirects = 0;
+    while args.redirect.follow() {
+        let mut next = match snapshot.next(&resp) {
+            Some(next) => next,
+            None => break,
+        };
+        redirects += 1;
+        if redirects > args.redirect.max_redirects() {
+            return Err(TooManyRedirects(args.redirect.max_redirects()).into());
+        }
 
-fn print_response_line(resp: &Response) {
-    println!("{}", (format!("{:?} {}", resp.version(), resp.status())).blue());
-}
+        // 中间响应里的 Set-Cookie 也要记录下来，并带到同一个服务器的下一个请求上
+        if let Some(session) = &mut session {
+            let headers = request::item_headers(args);
+            session.update(&headers, auth.as_ref(), &origin, &resp)?;
+            if redirect::same_origin(&origin, next.url()) {
+                if !headers.contains_key(COOKIE) {
+                    next.headers_mut().remove(COOKIE);
+                }
+                session.apply(&mut next);
+            }
+        }
 
-fn print_response_headers(resp: &Response) {
-    let headers = resp.headers();
+        if args.redirect.all() {
+            printer.print_response(resp).await?;
+            println!();
+            printer.print_request(&next)?;
+        }
 
-    for (name, value) in headers {
-        println!("{}: {:?}", name.to_string().green(), value);
+        snapshot = Snapshot::new(&next);
+        // 重定向后的请求没有 body 或者 body 可以复制，不需要重新构造
+        let rebuild = || async { Err(anyhow!("Failed to resend the redirected request")) };
+        resp = retry::send(&args.retry, next, |req| async { Ok(client.execute(req).await?) }, rebuild).await?;
     }
-}
+    let elapsed = start.elapsed();
 
-fn print_body(m: Option<Mime>, body: &str) {
-    if matches!(m, Some(v) if v == APPLICATION_JSON) {
-        let j_text = jsonxf::pretty_print(body);
-        if let Ok(j_text) = j_text {
-            println!("{}", j_text.cyan());
-            return;
+    let meta = if printer.meta() {
+        Some(Meta { elapsed, remote_addr: resp.remote_addr() })
+    } else {
+        None
+    };
+
+    if let Some(session) = &mut session {
+        session.update(&request::item_headers(args), auth.as_ref(), &origin, &resp)?;
+    }
+
+    let status = resp.status();
+
+    // 下载模式下响应 body 写入文件，不输出到终端
+    match download {
+        Some(download) => {
+            printer.print_response_head(&resp);
+            download.save(resp).await?;
         }
+        None => printer.print_response(resp).await?,
+    }
+
+    if let Some(meta) = &meta {
+        printer.print_meta(meta);
     }
 
-    println!("{}", body);
+    Ok(Some(status))
 }
 
-async fn print_response(resp: Response) -> Result<()> {
-    print_response_line(&resp);
-    print_response_headers(&resp);
+async fn run(opts: Opts) -> Result<ExitStatus> {
+    let method = opts.subcmd.method();
+    let mut args = opts.subcmd.into_args();
+    args.url = normalize_url(&args.url, &args.default_scheme)?;
 
-    let ct = get_content_type(&resp);
-    let body = resp.text().await?;
-    print_body(ct, &body);
+    let client = args.client.build()?;
 
-    Ok(())
+    let status = match send(client, method, &args).await? {
+        Some(status) if args.check_status => status,
+        _ => return Ok(ExitStatus::Success),
+    };
+
+    // 输出被重定向时，终端上看不到状态行，在 stderr 上提示一下
+    let exit = ExitStatus::from_status(status);
+    if exit != ExitStatus::Success && !atty::is(atty::Stream::Stdout) {
+        eprintln!("http: warning: HTTP {}", status);
+    }
+
+    Ok(exit)
 }
 
-fn get_content_type(resp: &Response) -> Option<Mime> {
-    resp.headers().get(header::CONTENT_TYPE).map(|v| v.to_str().unwrap().parse().unwrap())
+#[tokio::main]
+async fn main() {
+    let opts: Opts = Opts::parse();
+    // println!("{:?}", opts);
+
+    // 不同的错误使用不同的退出码，方便脚本判断
+    let exit = match run(opts).await {
+        Ok(exit) => exit,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            ExitStatus::from_error(&e)
+        }
+    };
+
+    std::process::exit(exit.code());
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::capture_server;
 
     #[test]
     fn parse_url_works() {
         assert!(parse_url("").is_err());
-        assert!(parse_url("abc").is_err());
-        assert!(parse_url("baidu.com").is_err());
+        assert!(parse_url("http://").is_err());
+        assert!(parse_url("http://bai du.com").is_err());
+        assert!(parse_url(":abc").is_err());
+        assert!(parse_url("abc").is_ok());
+        assert!(parse_url("baidu.com").is_ok());
         assert!(parse_url("http://baidu.com").is_ok());
         assert!(parse_url("http://baidu.com/").is_ok());
         assert!(parse_url("https://baidu.com/a/bc").is_ok());
     }
 
     #[test]
-    fn parse_kv_pair_works() {
-        assert!(parse_kv_pair("").is_err());
-        assert!(parse_kv_pair("a").is_err());
-        assert_eq!(parse_kv_pair("a=").ok(), Some(KvPair { k: "a".to_string(), v: "".to_string() }));
-        assert_eq!(parse_kv_pair("a=b").ok(), Some(KvPair { k: "a".to_string(), v: "b".to_string() }));
+    fn normalize_url_works() {
+        assert_eq!(normalize_url("baidu.com", "http").unwrap(), "http://baidu.com");
+        assert_eq!(normalize_url("baidu.com/a?b=1", "https").unwrap(), "https://baidu.com/a?b=1");
+        assert_eq!(normalize_url("localhost:8080/a", "http").unwrap(), "http://localhost:8080/a");
+        assert_eq!(normalize_url(":3000/foo", "http").unwrap(), "http://localhost:3000/foo");
+        assert_eq!(normalize_url(":/foo", "http").unwrap(), "http://localhost/foo");
+        assert_eq!(normalize_url(":", "http").unwrap(), "http://localhost");
+        assert_eq!(normalize_url("https://baidu.com", "http").unwrap(), "https://baidu.com");
     }
-}
-
-#[tokio::main]
-async fn main() -> Result<()> {
-    let opts: Opts = Opts::parse();
-    // println!("{:?}", opts);
 
-    let client = Client::new();
-
-    match opts.subcmd {
-        SubCommand::Get(ref args) => get(client, args).await?,
-        SubCommand::Post(ref args) => post(client, args).await?,
-    };
+    #[test]
+    fn parse_method_works() {
+        assert_eq!(parse_method("get").ok(), Some(Method::GET));
+        assert_eq!(parse_method("PURGE").ok(), Some(Method::from_bytes(b"PURGE").unwrap()));
+        assert!(parse_method("").is_err());
+        assert!(parse_method("a b").is_err());
+    }
 
-    Ok(())
+    #[tokio::test]
+    async fn session_ignores_cookies_from_other_origins() {
+        // 端口不同也是不同的服务器
+        let (other, other_requests) =
+            capture_server(b"HTTP/1.1 200 OK\r\nSet-Cookie: other=1\r\nContent-Length: 0\r\n\r\n").await;
+        let response = format!(
+            "HTTP/1.1 302 Found\r\nLocation: {}\r\nSet-Cookie: sid=1\r\nContent-Length: 0\r\n\r\n",
+            other
+        );
+        let (url, _) = capture_server(Box::leak(response.into_bytes().into_boxed_slice())).await;
+
+        let path = std::env::temp_dir().join(format!("httpie-redirect-session-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let args = RequestArgs::parse_from(["http", "--ignore-stdin", "--follow", "--session", path, &url]);
+        send(args.client.build().unwrap(), Method::GET, &args).await.unwrap();
+
+        let saved = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert!(saved.contains("\"sid\""));
+        assert!(!saved.contains("\"other\""));
+        assert!(!other_requests.lock().unwrap()[0].to_ascii_lowercase().contains("cookie"));
+    }
 }
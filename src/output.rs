@@ -0,0 +1,573 @@
+use anyhow::{anyhow, Result};
+use clap::{ArgEnum, Clap};
+use colored::*;
+use encoding_rs::{Encoding, UTF_8};
+use mime::Mime;
+use reqwest::header::{self, HeaderMap};
+use reqwest::{Request, Response, StatusCode, Version};
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::highlight::{self, Style, Syntax};
+
+// 输出相关的参数
+
+#[derive(Clap, Debug)]
+pub struct OutputArgs {
+    /// 输出哪些部分：H 请求头，B 请求 body，h 响应头，b 响应 body，m 元信息。终端上默认为 hb，重定向时为 b
+    #[clap(short, long, value_name = "WHAT")]
+    print: Option<Print>,
+    /// 只输出响应头，等价于 --print=h
+    #[clap(long, conflicts_with = "body")]
+    headers: bool,
+    /// 只输出响应 body，等价于 --print=b
+    #[clap(short, long)]
+    body: bool,
+    /// 同时输出发送的请求，等价于 --print=HBhb
+    #[clap(short, long)]
+    verbose: bool,
+    /// 额外输出响应的元信息：耗时和服务器地址，等价于在 --print 中加上 m
+    #[clap(short, long)]
+    meta: bool,
+    /// body 语法高亮的配色
+    #[clap(short, long, arg_enum, default_value = "ocean-dark")]
+    style: Style,
+    /// 输出的美化方式：all 着色并格式化，colors 只着色，format 只格式化，none 原样输出。
+    /// 终端上默认为 all，重定向时为 none
+    #[clap(long, arg_enum)]
+    pretty: Option<Pretty>,
+    /// 按名称排序输出请求头和响应头
+    #[clap(long)]
+    sorted: bool,
+}
+
+impl OutputArgs {
+    /// `offline` 时只有请求可以输出，默认输出改为请求头和请求 body
+    pub fn printer(&self, offline: bool) -> Printer {
+        let tty = atty::is(atty::Stream::Stdout);
+
+        // 没有指定 --pretty 时由 colored 根据终端和 NO_COLOR 等环境变量决定是否着色
+        let (colors, format) = match self.pretty {
+            Some(pretty) => {
+                let colors = matches!(pretty, Pretty::All | Pretty::Colors);
+                colored::control::set_override(colors);
+                (colors, matches!(pretty, Pretty::All | Pretty::Format))
+            }
+            None => (colored::control::SHOULD_COLORIZE.should_colorize(), tty),
+        };
+
+        Printer {
+            print: self.print(tty, offline),
+            style: self.style,
+            verbose: self.verbose,
+            colors,
+            format,
+            sorted: self.sorted,
+        }
+    }
+
+    fn print(&self, tty: bool, offline: bool) -> Print {
+        let print = match self.print {
+            Some(print) => print,
+            None if self.verbose => Print::ALL,
+            None if offline => "HB".parse().unwrap(),
+            None if self.headers => "h".parse().unwrap(),
+            None if self.body || !tty => "b".parse().unwrap(),
+            None => "hb".parse().unwrap(),
+        };
+
+        Print { meta: print.meta || self.meta, ..print }
+    }
+}
+
+// --pretty 的取值
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Pretty {
+    All,
+    Colors,
+    Format,
+    None,
+}
+
+/// --print 选择输出的部分
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Print {
+    pub request_headers: bool,
+    pub request_body: bool,
+    pub response_headers: bool,
+    pub response_body: bool,
+    pub meta: bool,
+}
+
+impl Print {
+    const ALL: Print = Print {
+        request_headers: true,
+        request_body: true,
+        response_headers: true,
+        response_body: true,
+        meta: false,
+    };
+}
+
+impl FromStr for Print {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(c) = s.chars().find(|c| !"HBhbm".contains(*c)) {
+            return Err(anyhow!("Invalid --print value {}: unknown part `{}`, expected H, B, h, b or m", s, c));
+        }
+
+        Ok(Self {
+            request_headers: s.contains('H'),
+            request_body: s.contains('B'),
+            response_headers: s.contains('h'),
+            response_body: s.contains('b'),
+            meta: s.contains('m'),
+        })
+    }
+}
+
+fn print_request_line(req: &Request) {
+    let url = req.url();
+    let path = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    println!("{}", format!("{} {} {}", req.method(), path, version_str(req.version())).blue());
+}
+
+fn print_response_line(resp: &Response) {
+    let status = resp.status();
+    println!("{} {}", version_str(resp.version()).blue(), status.to_string().color(status_color(status)));
+}
+
+// Version 的 Debug 输出为 HTTP/2.0 这样的形式，这里按协议中的写法输出
+fn version_str(version: Version) -> &'static str {
+    match version {
+        Version::HTTP_09 => "HTTP/0.9",
+        Version::HTTP_10 => "HTTP/1.0",
+        Version::HTTP_2 => "HTTP/2",
+        Version::HTTP_3 => "HTTP/3",
+        _ => "HTTP/1.1",
+    }
+}
+
+// 状态码按类别着色：2xx 绿色，3xx 黄色，4xx、5xx 红色，其它为蓝色
+fn status_color(status: StatusCode) -> Color {
+    if status.is_success() {
+        Color::Green
+    } else if status.is_redirection() {
+        Color::Yellow
+    } else if status.is_client_error() || status.is_server_error() {
+        Color::Red
+    } else {
+        Color::Blue
+    }
+}
+
+// 请求头名称按 HTTPie 的习惯显示为 Title-Case，值按文本输出，非 UTF-8 的字节会被替换
+fn print_headers(headers: &HeaderMap, sorted: bool) {
+    let mut lines: Vec<_> = headers
+        .iter()
+        .map(|(name, value)| (title_case(name.as_str()), String::from_utf8_lossy(value.as_bytes())))
+        .collect();
+    if sorted {
+        // 稳定排序，同名的多个请求头保持原来的顺序
+        lines.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+
+    for (name, value) in lines {
+        println!("{}: {}", name.green(), value);
+    }
+}
+
+pub(crate) fn title_case(name: &str) -> String {
+    name.split('-')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(c) => c.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// 按照 --print 和 --style 输出请求和响应
+#[derive(Debug, Clone, Copy)]
+pub struct Printer {
+    print: Print,
+    style: Style,
+    verbose: bool,
+    colors: bool,
+    format: bool,
+    sorted: bool,
+}
+
+impl Printer {
+    // 二进制内容在终端上只输出提示，重定向时原样写出
+    fn print_bytes(&self, m: Option<Mime>, body: &[u8]) -> Result<()> {
+        match body_kind(m.as_ref(), body) {
+            BodyKind::Empty => return Ok(()),
+            BodyKind::Text => {
+                self.print_body(m.clone(), &decode(m.as_ref(), body));
+                return Ok(());
+            }
+            BodyKind::Binary => {}
+        }
+
+        if atty::is(atty::Stream::Stdout) {
+            println!("{}", BINARY_SUPPRESSED_NOTICE);
+        } else {
+            let mut stdout = io::stdout();
+            stdout.write_all(body)?;
+            stdout.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn print_body(&self, m: Option<Mime>, body: &str) {
+        let syntax = match &m {
+            Some(m) if m.essence_str() != "text/plain" => Syntax::from_mime(m),
+            _ => Syntax::sniff(body),
+        };
+
+        let body = match syntax {
+            Some(Syntax::Json) if self.format => jsonxf::pretty_print(body).unwrap_or_else(|_| body.to_string()),
+            _ => body.to_string(),
+        };
+
+        match syntax {
+            Some(syntax) if self.colors => {
+                println!("{}", highlight::highlight(&body, syntax, self.style))
+            }
+            _ => println!("{}", body),
+        }
+    }
+
+    /// 输出即将发送的请求。Host 和 Content-Length 由 hyper 在发送时添加，这里提前补上
+    pub fn print_request(&self, req: &Request) -> Result<()> {
+        let print = self.print;
+        if print.request_headers {
+            print_request_line(req);
+            let mut headers = HeaderMap::new();
+            if !req.headers().contains_key(header::HOST) {
+                if let Some(host) = req.url().host_str() {
+                    let host = match req.url().port() {
+                        Some(port) => format!("{}:{}", host, port),
+                        None => host.to_string(),
+                    };
+                    if let Ok(host) = host.parse() {
+                        headers.insert(header::HOST, host);
+                    }
+                }
+            }
+            headers.extend(req.headers().clone());
+            if let Some(len) = req.body().and_then(|b| b.as_bytes()).map(|b| b.len()) {
+                if !headers.contains_key(header::CONTENT_LENGTH) && !headers.contains_key(header::TRANSFER_ENCODING) {
+                    headers.insert(header::CONTENT_LENGTH, len.into());
+                }
+            }
+            print_headers(&headers, self.sorted);
+        }
+
+        if print.request_body {
+            if let Some(body) = req.body() {
+                // 和 HTTP 报文一样，请求头和 body 之间空一行
+                if print.request_headers {
+                    println!();
+                }
+                // 流式 body（文件、multipart）在发送时才会读取，无法提前输出
+                match body.as_bytes() {
+                    Some(bytes) => self.print_bytes(self.content_type(req.headers()), bytes)?,
+                    None => println!("{}", "NOTE: streamed body not shown".yellow()),
+                }
+            }
+        }
+
+        if print.request_headers || print.request_body {
+            println!();
+        }
+
+        Ok(())
+    }
+
+    /// 只输出响应的状态行和响应头，下载模式下 body 会写入文件
+    pub fn print_response_head(&self, resp: &Response) {
+        if self.print.response_headers {
+            print_response_line(resp);
+            print_headers(resp.headers(), self.sorted);
+        }
+    }
+
+    pub async fn print_response(&self, resp: Response) -> Result<()> {
+        self.print_response_head(&resp);
+
+        if self.print.response_body {
+            let ct = self.content_type(resp.headers());
+            let body = resp.bytes().await?;
+            // HEAD、204 等没有 body 的响应什么也不输出，即使 Content-Type 是二进制类型
+            if body.is_empty() {
+                return Ok(());
+            }
+            if self.print.response_headers {
+                println!();
+            }
+            self.print_bytes(ct, &body)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn print_meta(&self, meta: &Meta) {
+        if !self.print.meta {
+            return;
+        }
+
+        if self.print.response_headers || self.print.response_body {
+            println!();
+        }
+        println!("{}: {:.3}s", "Elapsed time".green(), meta.elapsed.as_secs_f64());
+        if let Some(addr) = meta.remote_addr {
+            println!("{}: {}", "Remote address".green(), addr);
+        }
+    }
+
+    pub fn meta(&self) -> bool {
+        self.print.meta
+    }
+
+    // 无法解析的 Content-Type 视为未知类型，--verbose 时在 stderr 上给出提示
+    fn content_type(&self, headers: &HeaderMap) -> Option<Mime> {
+        match get_content_type(headers) {
+            Ok(m) => m,
+            Err(e) => {
+                if self.verbose {
+                    eprintln!("{} {}", "warning:".yellow(), e);
+                }
+                None
+            }
+        }
+    }
+}
+
+const BINARY_SUPPRESSED_NOTICE: &str = "\
++-----------------------------------------+
+| NOTE: binary data not shown in terminal |
++-----------------------------------------+";
+
+/// --meta 输出的响应元信息
+#[derive(Debug, Clone, PartialEq)]
+pub struct Meta {
+    /// 从发送请求到收到响应头的耗时
+    pub elapsed: Duration,
+    pub remote_addr: Option<SocketAddr>,
+}
+
+#[derive(Debug, PartialEq)]
+enum BodyKind {
+    Empty,
+    Text,
+    Binary,
+}
+
+// 空的 body 不需要输出，否则按照是否为二进制分别处理
+fn body_kind(m: Option<&Mime>, body: &[u8]) -> BodyKind {
+    if body.is_empty() {
+        BodyKind::Empty
+    } else if is_binary(m, body) {
+        BodyKind::Binary
+    } else {
+        BodyKind::Text
+    }
+}
+
+// 根据 Content-Type 和内容判断是否为二进制：图片、音视频等类型，或者包含 NUL、不是合法的 UTF-8
+fn is_binary(m: Option<&Mime>, body: &[u8]) -> bool {
+    if let Some(m) = m {
+        let binary_type = matches!(m.type_().as_str(), "image" | "audio" | "video" | "font") && m.suffix().is_none();
+        let binary_subtype = matches!(
+            m.essence_str(),
+            "application/octet-stream"
+                | "application/pdf"
+                | "application/zip"
+                | "application/gzip"
+                | "application/wasm"
+                | "application/protobuf"
+                | "application/x-protobuf"
+                | "application/vnd.google.protobuf"
+        );
+        if binary_type || binary_subtype {
+            return true;
+        }
+    }
+
+    if body.contains(&0) {
+        return true;
+    }
+
+    // 其它 charset 交给 decode 处理
+    match m.and_then(|m| m.get_param(mime::CHARSET)) {
+        Some(charset) if charset != mime::UTF_8 => false,
+        _ => std::str::from_utf8(body).is_err(),
+    }
+}
+
+// 按照 Content-Type 中的 charset 解码文本，默认为 UTF-8
+fn decode(m: Option<&Mime>, body: &[u8]) -> String {
+    let encoding = m
+        .and_then(|m| m.get_param(mime::CHARSET))
+        .and_then(|charset| Encoding::for_label(charset.as_str().as_bytes()))
+        .unwrap_or(UTF_8);
+    encoding.decode(body).0.into_owned()
+}
+
+fn get_content_type(headers: &HeaderMap) -> Result<Option<Mime>> {
+    let v = match headers.get(header::CONTENT_TYPE) {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+
+    let s = v
+        .to_str()
+        .map_err(|_| anyhow!("Content-Type {:?} is not valid ASCII, treating the body as unknown", v))?;
+    let m = s
+        .parse()
+        .map_err(|e| anyhow!("Invalid Content-Type {:?} ({}), treating the body as unknown", s, e))?;
+
+    Ok(Some(m))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::stub_server;
+    use reqwest::Method;
+
+    #[test]
+    fn parse_print_works() {
+        assert_eq!("HBhb".parse::<Print>().ok(), Some(Print::ALL));
+        assert_eq!(
+            "Hb".parse::<Print>().ok(),
+            Some(Print {
+                request_headers: true,
+                request_body: false,
+                response_headers: false,
+                response_body: true,
+                meta: false
+            })
+        );
+        assert_eq!("hm".parse::<Print>().ok().map(|p| (p.response_headers, p.meta)), Some((true, true)));
+        assert!("hx".parse::<Print>().is_err());
+    }
+
+    #[test]
+    fn default_print_works() {
+        let print = |s: &str| s.parse::<Print>().unwrap();
+        let args = OutputArgs::parse_from(["http"]);
+        assert_eq!(args.print(true, false), print("hb"));
+        assert_eq!(args.print(false, false), print("b"));
+        assert_eq!(args.print(true, true), print("HB"));
+
+        let args = OutputArgs::parse_from(["http", "-p", "h", "--meta"]);
+        assert_eq!(args.print(true, true), print("hm"));
+    }
+
+    #[test]
+    fn version_str_works() {
+        assert_eq!(version_str(Version::HTTP_10), "HTTP/1.0");
+        assert_eq!(version_str(Version::HTTP_11), "HTTP/1.1");
+        assert_eq!(version_str(Version::HTTP_2), "HTTP/2");
+        assert_eq!(version_str(Version::HTTP_3), "HTTP/3");
+    }
+
+    #[test]
+    fn status_color_works() {
+        assert_eq!(status_color(StatusCode::OK), Color::Green);
+        assert_eq!(status_color(StatusCode::NO_CONTENT), Color::Green);
+        assert_eq!(status_color(StatusCode::FOUND), Color::Yellow);
+        assert_eq!(status_color(StatusCode::NOT_FOUND), Color::Red);
+        assert_eq!(status_color(StatusCode::BAD_GATEWAY), Color::Red);
+        assert_eq!(status_color(StatusCode::CONTINUE), Color::Blue);
+    }
+
+    #[test]
+    fn title_case_works() {
+        assert_eq!(title_case("content-type"), "Content-Type");
+        assert_eq!(title_case("x-request-id"), "X-Request-Id");
+        assert_eq!(title_case("etag"), "Etag");
+        assert_eq!(title_case("a--b"), "A--B");
+    }
+
+    #[test]
+    fn get_content_type_works() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(get_content_type(&headers).ok(), Some(None));
+
+        headers.insert(header::CONTENT_TYPE, header::HeaderValue::from_static("application/json; charset=utf-8"));
+        assert_eq!(get_content_type(&headers).ok().flatten().map(|m| m.essence_str().to_string()), Some("application/json".into()));
+
+        headers.insert(header::CONTENT_TYPE, header::HeaderValue::from_static("not a mime"));
+        assert!(get_content_type(&headers).is_err());
+
+        headers.insert(header::CONTENT_TYPE, header::HeaderValue::from_bytes(b"text/\xff").unwrap());
+        assert!(get_content_type(&headers).is_err());
+    }
+
+    #[test]
+    fn is_binary_works() {
+        let mime = |s: &str| s.parse::<Mime>().unwrap();
+        assert!(is_binary(Some(&mime("image/png")), b"text"));
+        assert!(is_binary(Some(&mime("application/x-protobuf")), b"text"));
+        assert!(!is_binary(Some(&mime("image/svg+xml")), b"<svg/>"));
+        assert!(is_binary(None, b"a\0b"));
+        assert!(is_binary(Some(&mime("text/plain")), b"\xff\xfe"));
+        assert!(!is_binary(Some(&mime("text/plain; charset=latin1")), b"caf\xe9"));
+        assert!(!is_binary(None, "你好".as_bytes()));
+        assert_eq!(decode(Some(&mime("text/plain; charset=latin1")), b"caf\xe9"), "café");
+    }
+
+    #[tokio::test]
+    async fn empty_binary_body_is_not_printed() {
+        let png = Some(mime::IMAGE_PNG);
+        assert_eq!(body_kind(png.as_ref(), b""), BodyKind::Empty);
+        assert_eq!(body_kind(png.as_ref(), b"\x89PNG"), BodyKind::Binary);
+        assert_eq!(body_kind(None, b"{}"), BodyKind::Text);
+
+        let head = b"HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: 1024\r\n\r\n";
+        let no_content = b"HTTP/1.1 204 No Content\r\nContent-Type: image/png\r\n\r\n";
+        let client = reqwest::Client::new();
+        for (method, response) in [(Method::HEAD, &head[..]), (Method::GET, &no_content[..])] {
+            let resp = client.request(method, stub_server(response).await).send().await.unwrap();
+            let ct = get_content_type(resp.headers()).unwrap();
+            assert_eq!(body_kind(ct.as_ref(), &resp.bytes().await.unwrap()), BodyKind::Empty);
+        }
+    }
+
+    #[tokio::test]
+    async fn print_response_with_bad_content_type() {
+        let printer = Printer {
+            print: Print::ALL,
+            style: Style::OceanDark,
+            verbose: true,
+            colors: true,
+            format: true,
+            sorted: false,
+        };
+        let responses: [&'static [u8]; 3] = [
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/\xff\xfe\r\nContent-Length: 2\r\n\r\nhi",
+            b"HTTP/1.1 200 OK\r\nContent-Type: ;;;\r\nContent-Length: 8\r\n\r\n{\"a\": 1}",
+            b"HTTP/1.1 200 OK\r\nContent-Type: application/json; charset=nope\r\nContent-Length: 2\r\n\r\n{}",
+        ];
+
+        for response in responses {
+            let url = stub_server(response).await;
+            let resp = reqwest::get(&url).await.unwrap();
+            assert!(printer.print_response(resp).await.is_ok());
+        }
+    }
+}
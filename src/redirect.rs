@@ -0,0 +1,154 @@
+use clap::Clap;
+use reqwest::header::{
+    HeaderMap, AUTHORIZATION, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, LOCATION, PROXY_AUTHORIZATION,
+    TRANSFER_ENCODING, WWW_AUTHENTICATE,
+};
+use reqwest::{Method, Request, Response, StatusCode, Url};
+use std::fmt;
+
+// 重定向相关的参数。reqwest 自动跟随时拿不到中间的响应，所以关闭它的重定向，由我们逐个跟随
+
+#[derive(Clap, Debug)]
+pub struct RedirectArgs {
+    /// 跟随 3xx 重定向，默认直接输出重定向的响应
+    #[clap(short = 'F', long)]
+    follow: bool,
+    /// 跟随重定向时最多跟随的次数，超过时以 6 退出
+    #[clap(long, value_name = "N", default_value = "30")]
+    max_redirects: usize,
+    /// 跟随重定向时输出中间的每个请求和响应
+    #[clap(long, requires = "follow")]
+    all: bool,
+}
+
+impl RedirectArgs {
+    pub fn follow(&self) -> bool {
+        self.follow
+    }
+
+    pub fn max_redirects(&self) -> usize {
+        self.max_redirects
+    }
+
+    pub fn all(&self) -> bool {
+        self.all
+    }
+}
+
+/// 重定向次数超过 --max-redirects
+#[derive(Debug)]
+pub struct TooManyRedirects(pub usize);
+
+impl fmt::Display for TooManyRedirects {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Too many redirects (--max-redirects={})", self.0)
+    }
+}
+
+impl std::error::Error for TooManyRedirects {}
+
+/// 发送前保存的请求副本，用来构造重定向后的请求。流式 body 无法复制，只保留方法、URL 和请求头
+pub struct Snapshot {
+    req: Request,
+    streamed: bool,
+}
+
+impl Snapshot {
+    pub fn new(req: &Request) -> Self {
+        match req.try_clone() {
+            Some(req) => Snapshot { req, streamed: false },
+            None => {
+                let mut copy = Request::new(req.method().clone(), req.url().clone());
+                *copy.headers_mut() = req.headers().clone();
+                Snapshot { req: copy, streamed: true }
+            }
+        }
+    }
+
+    /// 按照 reqwest 的规则构造重定向后的请求，不是重定向或者无法跟随时返回 None
+    pub fn next(self, resp: &Response) -> Option<Request> {
+        let mut req = self.req;
+        match resp.status() {
+            // 301、302、303 改用 GET，并丢弃 body
+            StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND | StatusCode::SEE_OTHER => {
+                *req.body_mut() = None;
+                for name in &[TRANSFER_ENCODING, CONTENT_ENCODING, CONTENT_TYPE, CONTENT_LENGTH] {
+                    req.headers_mut().remove(name);
+                }
+                if req.method() != Method::HEAD {
+                    *req.method_mut() = Method::GET;
+                }
+            }
+            // 307、308 需要原样重新发送 body
+            StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT if !self.streamed => {}
+            _ => return None,
+        }
+
+        let location = resp.headers().get(LOCATION)?.to_str().ok()?;
+        let next = resp.url().join(location).ok()?;
+        if !same_origin(req.url(), &next) {
+            remove_sensitive_headers(req.headers_mut());
+        }
+        *req.url_mut() = next;
+
+        Some(req)
+    }
+}
+
+pub fn same_origin(a: &Url, b: &Url) -> bool {
+    a.host_str() == b.host_str() && a.port_or_known_default() == b.port_or_known_default()
+}
+
+// 跳转到其它服务器时不携带认证信息和 cookie
+fn remove_sensitive_headers(headers: &mut HeaderMap) {
+    for name in &[AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, WWW_AUTHENTICATE] {
+        headers.remove(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::stub_server;
+
+    async fn redirect(method: Method, response: &'static [u8]) -> Option<Request> {
+        let url: Url = stub_server(response).await.parse().unwrap();
+        let client = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()).build().unwrap();
+        let req = client
+            .request(method, url)
+            .header(AUTHORIZATION, "Basic dTpw")
+            .header(CONTENT_TYPE, "application/json")
+            .body("{}")
+            .build()
+            .unwrap();
+        let snapshot = Snapshot::new(&req);
+        let resp = client.execute(req).await.unwrap();
+        snapshot.next(&resp)
+    }
+
+    #[tokio::test]
+    async fn next_request_works() {
+        let req = redirect(Method::POST, b"HTTP/1.1 302 Found\r\nLocation: /next?a=1\r\nContent-Length: 0\r\n\r\n")
+            .await
+            .unwrap();
+        assert_eq!(req.method(), Method::GET);
+        assert_eq!(req.url().path(), "/next");
+        assert_eq!(req.url().query(), Some("a=1"));
+        assert!(req.body().is_none());
+        assert!(req.headers().get(CONTENT_TYPE).is_none());
+        assert!(req.headers().get(AUTHORIZATION).is_some());
+
+        let req = redirect(
+            Method::POST,
+            b"HTTP/1.1 307 Temporary Redirect\r\nLocation: http://other.example/\r\nContent-Length: 0\r\n\r\n",
+        )
+        .await
+        .unwrap();
+        assert_eq!(req.method(), Method::POST);
+        assert_eq!(req.body().and_then(|b| b.as_bytes()), Some(&b"{}"[..]));
+        assert!(req.headers().get(AUTHORIZATION).is_none());
+
+        assert!(redirect(Method::GET, b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").await.is_none());
+        assert!(redirect(Method::GET, b"HTTP/1.1 302 Found\r\nContent-Length: 0\r\n\r\n").await.is_none());
+    }
+}
@@ -0,0 +1,471 @@
+use anyhow::{anyhow, Context, Result};
+use mime::Mime;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, CONTENT_LENGTH, CONTENT_TYPE, HOST, USER_AGENT};
+use reqwest::multipart::{Form, Part};
+use reqwest::{Body, Client, Method, RequestBuilder};
+use serde_json::Value;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+use tokio_util::io::ReaderStream;
+
+use crate::items::{FileItem, RequestItem};
+use crate::json;
+use crate::RequestArgs;
+
+// httpie 默认发送的请求头。reqwest 在发送时也会补上 Accept，这里显式设置以便 --verbose 能看到
+fn default_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(ACCEPT, HeaderValue::from_static("*/*"));
+    headers.insert(USER_AGENT, HeaderValue::from_static(concat!("httpie/", env!("CARGO_PKG_VERSION"))));
+    headers
+}
+
+/// --remove-header 的值。Accept 会被 reqwest 在发送时补上，Host 会被 hyper 补上，不能去掉
+pub fn parse_removable_header(s: &str) -> Result<HeaderName> {
+    let name: HeaderName = s.parse().map_err(|_| anyhow!("Invalid header name {}", s))?;
+    if name == ACCEPT || name == HOST {
+        return Err(anyhow!("{} is always sent and cannot be removed", s));
+    }
+
+    Ok(name)
+}
+
+// 请求 body 的编码方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BodyMode {
+    Json,
+    Form,
+    Multipart,
+}
+
+impl RequestArgs {
+    pub fn body_mode(&self) -> BodyMode {
+        if self.multipart {
+            BodyMode::Multipart
+        } else if self.form {
+            BodyMode::Form
+        } else {
+            BodyMode::Json
+        }
+    }
+}
+
+/// 命令行上通过 `Header:Value` 指定的请求头
+pub fn item_headers(args: &RequestArgs) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for item in args.items.iter() {
+        if let RequestItem::Header(name, value) = item {
+            headers.append(name, value.clone());
+        }
+    }
+    headers
+}
+
+/// 根据命令行参数构造请求：请求头、查询参数和 body 分别交给 reqwest 对应的部分
+pub async fn build(client: &Client, method: Method, args: &RequestArgs) -> Result<RequestBuilder> {
+    let mut req = client.request(method, &args.url);
+
+    let mut query = Vec::new();
+    let mut data = Vec::new();
+    let mut files = Vec::new();
+    let mut body_file = None;
+    let mut headers = item_headers(args);
+
+    for item in args.items.iter() {
+        match item {
+            RequestItem::Header(..) => {}
+            RequestItem::Query(name, value) => query.push((name, value)),
+            RequestItem::Data(name, value) => data.push((name.as_str(), Value::String(value.clone()))),
+            RequestItem::Json(name, value) => data.push((name.as_str(), value.clone())),
+            RequestItem::File(file) => files.push(file),
+            RequestItem::Body(path) => {
+                if body_file.replace(path).is_some() {
+                    return Err(anyhow!("Only one @file body is allowed"));
+                }
+            }
+        }
+    }
+
+    if !query.is_empty() {
+        req = req.query(&query);
+    }
+
+    // 默认请求头可以被同名的请求项覆盖，或者用 --remove-header 去掉
+    let has_content_type = headers.contains_key(CONTENT_TYPE);
+    for (name, value) in default_headers().iter() {
+        if !headers.contains_key(name) && !args.remove_header.contains(name) {
+            headers.insert(name, value.clone());
+        }
+    }
+    req = req.headers(headers);
+
+    let mode = args.body_mode();
+
+    // 原始 body 不能和数据字段混用，Content-Type 根据模式或文件扩展名推断，也可以用请求头覆盖
+    if let Some(raw) = raw_body(args, body_file).await? {
+        if !data.is_empty() || !files.is_empty() {
+            return Err(anyhow!(
+                "Request body (from stdin, --raw or @file) and data fields cannot be mixed"
+            ));
+        }
+        if mode == BodyMode::Multipart {
+            return Err(anyhow!("Request body (from stdin, --raw or @file) cannot be sent with --multipart"));
+        }
+        let mime = match (&raw, mode) {
+            (RawBody::File(path), _) => guess_mime(path),
+            (_, BodyMode::Form) => Some(mime::APPLICATION_WWW_FORM_URLENCODED),
+            _ => Some(mime::APPLICATION_JSON),
+        };
+        if let (Some(mime), false) = (mime, has_content_type) {
+            req = req.header(CONTENT_TYPE, mime.as_ref());
+        }
+        return raw.apply(req, !args.offline).await;
+    }
+
+    // 有文件时总是发送 multipart body
+    let mode = match mode {
+        _ if !files.is_empty() => BodyMode::Multipart,
+        mode => mode,
+    };
+
+    let req = match mode {
+        // 只有提供了数据字段时才发送 json body
+        BodyMode::Json if data.is_empty() => req,
+        BodyMode::Json => req.json(&json::build_body(data)?),
+        BodyMode::Form => req.form(&form_fields(data)?),
+        // --offline 时不会发送请求，直接在内存中编码出完整的 body 以便输出
+        BodyMode::Multipart if args.offline => {
+            let (boundary, body) = multipart_body(form_fields(data)?, files).await?;
+            req.header(CONTENT_TYPE, format!("multipart/form-data; boundary={}", boundary)).body(body)
+        }
+        BodyMode::Multipart => {
+            let mut form = Form::new();
+            for (name, value) in form_fields(data)? {
+                form = form.text(name.to_string(), value);
+            }
+            for file in files {
+                form = form.part(file.name.clone(), file_part(file).await?);
+            }
+            req.multipart(form)
+        }
+    };
+
+    Ok(req)
+}
+
+// 表单只能包含字符串字段
+fn form_fields(data: Vec<(&str, Value)>) -> Result<Vec<(&str, String)>> {
+    data.into_iter()
+        .map(|(name, value)| match value {
+            Value::String(v) => Ok((name, v)),
+            _ => Err(anyhow!("JSON field `{}` is not allowed in a form body", name)),
+        })
+        .collect()
+}
+
+// 文件以流的方式从磁盘读取，不会一次性读入内存
+async fn file_part(file: &FileItem) -> Result<Part> {
+    let (body, len) = open_file(&file.path).await?;
+
+    let mut part = Part::stream_with_length(body, len);
+    if let Some(name) = file.path.file_name() {
+        part = part.file_name(name.to_string_lossy().into_owned());
+    }
+    if let Some(mime) = file_mime(file) {
+        part = part.mime_str(mime.as_ref())?;
+    }
+
+    Ok(part)
+}
+
+fn file_mime(file: &FileItem) -> Option<Mime> {
+    file.mime.clone().or_else(|| guess_mime(&file.path))
+}
+
+// 和 reqwest 的 multipart 编码格式相同：先是文本字段，然后是文件
+async fn multipart_body(fields: Vec<(&str, String)>, files: Vec<&FileItem>) -> Result<(String, Vec<u8>)> {
+    let boundary = Form::new().boundary().to_string();
+    let mut body = Vec::new();
+    for (name, value) in fields {
+        write!(body, "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n", boundary, name, value)?;
+    }
+    for file in files {
+        write!(body, "--{}\r\nContent-Disposition: form-data; name=\"{}\"", boundary, file.name)?;
+        if let Some(name) = file.path.file_name() {
+            write!(body, "; filename=\"{}\"", name.to_string_lossy())?;
+        }
+        if let Some(mime) = file_mime(file) {
+            write!(body, "\r\nContent-Type: {}", mime)?;
+        }
+        body.extend_from_slice(b"\r\n\r\n");
+        body.extend(read_file(&file.path).await?);
+        body.extend_from_slice(b"\r\n");
+    }
+    write!(body, "--{}--\r\n", boundary)?;
+
+    Ok((boundary, body))
+}
+
+async fn read_file(path: &Path) -> Result<Vec<u8>> {
+    tokio::fs::read(path).await.with_context(|| format!("Failed to read {}", path.display()))
+}
+
+async fn open_file(path: &Path) -> Result<(Body, u64)> {
+    let open_err = || format!("Failed to read {}", path.display());
+    let f = File::open(path).await.with_context(open_err)?;
+    let len = f.metadata().await.with_context(open_err)?.len();
+
+    Ok((Body::wrap_stream(ReaderStream::new(f)), len))
+}
+
+// 不经过请求项解析、原样发送的 body
+enum RawBody {
+    Bytes(Vec<u8>),
+    File(PathBuf),
+}
+
+impl RawBody {
+    // stream 为 false 时（--offline）把文件整个读出，以便输出 body
+    async fn apply(self, req: RequestBuilder, stream: bool) -> Result<RequestBuilder> {
+        match self {
+            RawBody::Bytes(v) => Ok(req.body(v)),
+            RawBody::File(path) if !stream => Ok(req.body(read_file(&path).await?)),
+            // 流式 body 默认使用 chunked 编码，这里显式给出长度
+            RawBody::File(path) => {
+                let (body, len) = open_file(&path).await?;
+                Ok(req.header(CONTENT_LENGTH, len).body(body))
+            }
+        }
+    }
+}
+
+// 依次尝试 --raw、@file 和管道输入的 stdin，最多只能有一个来源
+async fn raw_body(args: &RequestArgs, body_file: Option<&PathBuf>) -> Result<Option<RawBody>> {
+    let mut sources = Vec::new();
+    if let Some(raw) = &args.raw {
+        sources.push(RawBody::Bytes(raw.clone().into_bytes()));
+    }
+    if let Some(path) = body_file {
+        sources.push(RawBody::File(path.clone()));
+    }
+    if !args.ignore_stdin && atty::isnt(atty::Stream::Stdin) {
+        let mut buf = Vec::new();
+        tokio::io::stdin().read_to_end(&mut buf).await.context("Failed to read stdin")?;
+        // 空的 stdin（比如 < /dev/null）视为没有 body
+        if !buf.is_empty() {
+            sources.push(RawBody::Bytes(buf));
+        }
+    }
+
+    if sources.len() > 1 {
+        return Err(anyhow!("Only one of stdin, --raw and @file can be used as the request body"));
+    }
+
+    Ok(sources.pop())
+}
+
+// 根据文件扩展名推断 Content-Type
+pub fn guess_mime(path: &Path) -> Option<Mime> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    let mime = match ext.as_str() {
+        "json" => mime::APPLICATION_JSON,
+        "txt" => mime::TEXT_PLAIN,
+        "html" | "htm" => mime::TEXT_HTML,
+        "xml" => mime::TEXT_XML,
+        "csv" => mime::TEXT_CSV,
+        "css" => mime::TEXT_CSS,
+        "js" => mime::APPLICATION_JAVASCRIPT,
+        "yaml" | "yml" => "application/x-yaml".parse().ok()?,
+        "png" => mime::IMAGE_PNG,
+        "jpg" | "jpeg" => mime::IMAGE_JPEG,
+        "gif" => mime::IMAGE_GIF,
+        "svg" => mime::IMAGE_SVG,
+        "pdf" => mime::APPLICATION_PDF,
+        _ => return None,
+    };
+
+    Some(mime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::capture_server;
+    use clap::Clap;
+
+    // 用和命令行一样的方式构造请求并发送，返回服务器收到的请求
+    async fn send(method: Method, args: &[&str]) -> Result<String> {
+        let (url, requests) = capture_server(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").await;
+        let args = RequestArgs::parse_from(["http", "--ignore-stdin", &url].iter().chain(args));
+        let client = args.client.build()?;
+        let req = build(&client, method, &args).await?.build()?;
+        client.execute(req).await?;
+
+        let request = requests.lock().unwrap().pop().unwrap();
+        Ok(request)
+    }
+
+    // 请求头的名字统一为小写，方便比较
+    async fn send_headers(args: &[&str]) -> String {
+        send(Method::GET, args).await.unwrap().to_ascii_lowercase()
+    }
+
+    fn content_type_of(request: &str) -> &str {
+        let line = request.lines().find(|l| l.to_ascii_lowercase().starts_with("content-type:")).unwrap();
+        line["content-type:".len()..].trim()
+    }
+
+    fn body(request: &str) -> &str {
+        request.split_once("\r\n\r\n").unwrap().1
+    }
+
+    #[tokio::test]
+    async fn headers_work() {
+        let request = send_headers(&["X-Empty;", "X-Colon:", "X-A:1", "X-A:2"]).await;
+        assert!(request.contains("\r\nx-empty: \r\n"));
+        assert!(request.contains("\r\nx-colon: \r\n"));
+        assert!(request.contains("\r\nx-a: 1\r\nx-a: 2\r\n"));
+        assert!(request.contains("\r\nuser-agent: httpie/"));
+
+        let request = send_headers(&["--remove-header", "User-Agent", "Accept:application/json"]).await;
+        assert!(!request.contains("user-agent"));
+        assert!(request.contains("\r\naccept: application/json\r\n"));
+        assert!(!request.contains("*/*"));
+    }
+
+    #[tokio::test]
+    async fn form_works() {
+        let request = send(Method::POST, &["--form", "a=1", "b=x y&z", "q==1"]).await.unwrap();
+        assert!(request.starts_with("POST /?q=1 "));
+        assert_eq!(content_type_of(&request), "application/x-www-form-urlencoded");
+        assert_eq!(body(&request), "a=1&b=x+y%26z");
+
+        let err = send(Method::POST, &["--form", "a:=1"]).await.unwrap_err();
+        assert!(err.to_string().contains("JSON field `a` is not allowed"));
+    }
+
+    #[tokio::test]
+    async fn multipart_works() {
+        let dir = std::env::temp_dir().join(format!("httpie-multipart-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let json = dir.join("a.json");
+        let text = dir.join("b.dat");
+        std::fs::write(&json, "{}").unwrap();
+        std::fs::write(&text, "hello").unwrap();
+
+        // --form 遇到文件时自动改用 multipart，文件类型可以推断或者用 ;type= 指定
+        let file = format!("f@{}", json.display());
+        let typed = format!("g@{};type=text/plain", text.display());
+        let request = send(Method::POST, &["--form", "a=1", &file, &typed]).await.unwrap();
+        let boundary = content_type_of(&request).strip_prefix("multipart/form-data; boundary=").unwrap();
+        let body = body(&request);
+        assert!(body.starts_with(&format!("--{}\r\n", boundary)));
+        assert!(body.contains("Content-Disposition: form-data; name=\"a\"\r\n\r\n1\r\n"));
+        assert!(body.contains(
+            "Content-Disposition: form-data; name=\"f\"; filename=\"a.json\"\r\nContent-Type: application/json\r\n\r\n{}\r\n"
+        ));
+        assert!(body.contains(
+            "Content-Disposition: form-data; name=\"g\"; filename=\"b.dat\"\r\nContent-Type: text/plain\r\n\r\nhello\r\n"
+        ));
+        assert!(body.find("name=\"a\"") < body.find("name=\"f\""));
+
+        // --multipart 没有文件时也发送 multipart
+        let request = send(Method::POST, &["--multipart", "a=1"]).await.unwrap();
+        assert!(content_type_of(&request).starts_with("multipart/form-data; boundary="));
+        assert!(send(Method::POST, &["--multipart", "a:=[1]"]).await.is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn raw_body_works() {
+        let request = send(Method::POST, &["--raw", "x y"]).await.unwrap();
+        assert_eq!(content_type_of(&request), "application/json");
+        assert_eq!(body(&request), "x y");
+
+        let request = send(Method::POST, &["--form", "--raw", "a=1"]).await.unwrap();
+        assert_eq!(content_type_of(&request), "application/x-www-form-urlencoded");
+        assert_eq!(body(&request), "a=1");
+
+        let dir = std::env::temp_dir().join(format!("httpie-raw-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let json = dir.join("a.json");
+        let unknown = dir.join("a.bin");
+        std::fs::write(&json, r#"{"a":1}"#).unwrap();
+        std::fs::write(&unknown, "data").unwrap();
+        let json = format!("@{}", json.display());
+
+        // Content-Type 根据文件扩展名推断，请求项中的 Content-Type 优先
+        let request = send(Method::POST, &[&json]).await.unwrap();
+        assert_eq!(content_type_of(&request), "application/json");
+        assert_eq!(body(&request), r#"{"a":1}"#);
+        assert!(request.to_ascii_lowercase().contains("\r\ncontent-length: 7\r\n"));
+
+        let request = send(Method::POST, &[&json, "Content-Type:text/plain"]).await.unwrap();
+        assert_eq!(content_type_of(&request), "text/plain");
+
+        let request = send(Method::POST, &[&format!("@{}", unknown.display())]).await.unwrap();
+        assert!(!request.to_ascii_lowercase().contains("content-type"));
+        assert_eq!(body(&request), "data");
+
+        let err = send(Method::POST, &[&json, "a=1"]).await.unwrap_err();
+        assert!(err.to_string().contains("cannot be mixed"));
+        let err = send(Method::POST, &["--raw", "x", &json]).await.unwrap_err();
+        assert!(err.to_string().contains("Only one of stdin, --raw and @file"));
+        let err = send(Method::POST, &[&json, &json]).await.unwrap_err();
+        assert!(err.to_string().contains("Only one @file body"));
+        assert!(send(Method::POST, &["--multipart", "--raw", "x"]).await.is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    async fn offline(args: &[&str]) -> reqwest::Request {
+        let args = RequestArgs::parse_from(["http", "--ignore-stdin", "--offline", "http://example.com/"].iter().chain(args));
+        build(&Client::new(), Method::POST, &args).await.unwrap().build().unwrap()
+    }
+
+    #[tokio::test]
+    async fn offline_reads_file_bodies() {
+        let dir = std::env::temp_dir().join(format!("httpie-offline-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let json = dir.join("a.json");
+        std::fs::write(&json, r#"{"a":1}"#).unwrap();
+
+        let req = offline(&[&format!("@{}", json.display())]).await;
+        assert_eq!(req.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(req.body().and_then(|b| b.as_bytes()), Some(&br#"{"a":1}"#[..]));
+
+        // 输出的 multipart body 和真正发送的一致，只有 boundary 不同
+        let items = ["--multipart", "a=1", &format!("f@{}", json.display())];
+        let req = offline(&items).await;
+        let content_type = req.headers()[CONTENT_TYPE].to_str().unwrap();
+        let boundary = content_type.strip_prefix("multipart/form-data; boundary=").unwrap();
+        let printed = String::from_utf8(req.body().and_then(|b| b.as_bytes()).unwrap().to_vec()).unwrap();
+
+        let request = send(Method::POST, &items).await.unwrap();
+        let sent_boundary = content_type_of(&request).strip_prefix("multipart/form-data; boundary=").unwrap();
+        assert_eq!(printed.replace(boundary, "BOUNDARY"), body(&request).replace(sent_boundary, "BOUNDARY"));
+        assert!(printed.contains(r#"{"a":1}"#));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parse_removable_header_works() {
+        assert_eq!(parse_removable_header("User-Agent").ok(), Some(USER_AGENT));
+        assert!(parse_removable_header("Accept").is_err());
+        assert!(parse_removable_header("host").is_err());
+        assert!(parse_removable_header("bad header").is_err());
+    }
+
+    #[test]
+    fn guess_mime_works() {
+        assert_eq!(guess_mime(Path::new("a.json")), Some(mime::APPLICATION_JSON));
+        assert_eq!(guess_mime(Path::new("dir/a.HTML")), Some(mime::TEXT_HTML));
+        assert_eq!(guess_mime(Path::new("a.yml")).map(|m| m.to_string()), Some("application/x-yaml".into()));
+        assert_eq!(guess_mime(Path::new("a.unknown")), None);
+        assert_eq!(guess_mime(Path::new("Makefile")), None);
+    }
+}
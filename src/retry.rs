@@ -0,0 +1,163 @@
+use anyhow::Result;
+use clap::Clap;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Method, Request, Response, StatusCode};
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, SystemTime};
+
+// 重试相关的参数
+
+#[derive(Clap, Debug)]
+pub struct RetryArgs {
+    /// 连接错误、超时或者响应状态码在 --retry-status 中时，最多重试的次数
+    #[clap(long, value_name = "N", default_value = "0")]
+    retries: u32,
+    /// 需要重试的响应状态码，用逗号分隔。响应中有 Retry-After 时按它等待
+    #[clap(long, value_name = "CODES", use_delimiter = true, default_values = &["429", "502", "503", "504"])]
+    retry_status: Vec<StatusCode>,
+}
+
+// 指数退避的初始间隔和上限
+const BACKOFF_BASE: Duration = Duration::from_millis(500);
+const BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+/// 发送请求，失败时按照 --retries 等待后重新发送。请求能复制时复制一份，否则通过 `rebuild` 重新构造
+pub async fn send<S, SFut, B, BFut>(args: &RetryArgs, req: Request, send: S, rebuild: B) -> Result<Response>
+where
+    S: Fn(Request) -> SFut,
+    SFut: Future<Output = Result<Response>>,
+    B: Fn() -> BFut,
+    BFut: Future<Output = Result<Request>>,
+{
+    let mut req = req;
+    let mut attempt = 0;
+
+    loop {
+        let retry = if attempt < args.retries { req.try_clone() } else { None };
+        let method = req.method().clone();
+        let result = send(req).await;
+        if attempt >= args.retries {
+            return result;
+        }
+
+        let (reason, delay) = match &result {
+            Ok(resp) if args.retry_status.contains(&resp.status()) => match retry_after(resp) {
+                // 服务器要求等待太久时不再重试，直接返回这次的响应
+                Some(delay) if delay > BACKOFF_MAX => return result,
+                delay => (format!("HTTP {}", resp.status()), delay.unwrap_or_else(|| backoff(attempt))),
+            },
+            Err(e) if is_retryable(e, &method) => (e.root_cause().to_string(), backoff(attempt)),
+            _ => return result,
+        };
+
+        attempt += 1;
+        eprintln!(
+            "http: warning: {}, retrying in {:.1}s ({}/{})",
+            reason,
+            delay.as_secs_f64(),
+            attempt,
+            args.retries
+        );
+        tokio::time::sleep(delay).await;
+
+        req = match retry {
+            Some(req) => req,
+            None => rebuild().await?,
+        };
+    }
+}
+
+// 连接错误时请求还没有发出，总是可以重试。超时的请求可能已经被服务器处理，只重试幂等的方法。
+// 其它错误（如 URL、TLS 证书错误）重试也没有意义
+fn is_retryable(err: &anyhow::Error, method: &Method) -> bool {
+    err.chain()
+        .find_map(|e| e.downcast_ref::<reqwest::Error>())
+        .is_some_and(|e| e.is_connect() || (e.is_timeout() && is_idempotent(method)))
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE | Method::PUT | Method::DELETE
+    )
+}
+
+// 第 n 次重试前等待 BACKOFF_BASE * 2^n，不超过 BACKOFF_MAX，并随机减少最多一半避免同时重试
+fn backoff(attempt: u32) -> Duration {
+    let delay = BACKOFF_BASE.saturating_mul(2u32.saturating_pow(attempt)).min(BACKOFF_MAX);
+    delay.mul_f64(1.0 - jitter() / 2.0)
+}
+
+// [0, 1) 之间的随机数，不要求随机性的质量
+fn jitter() -> f64 {
+    let n = RandomState::new().build_hasher().finish();
+    (n >> 11) as f64 / (1u64 << 53) as f64
+}
+
+// Retry-After 可以是秒数，也可以是 HTTP 日期
+fn retry_after(resp: &Response) -> Option<Duration> {
+    parse_retry_after(resp.headers().get(RETRY_AFTER)?.to_str().ok()?, SystemTime::now())
+}
+
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(now).unwrap_or(Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::capture_server;
+    use std::time::Instant;
+
+    #[test]
+    fn backoff_works() {
+        for attempt in 0..3 {
+            let max = BACKOFF_BASE * 2u32.pow(attempt);
+            let delay = backoff(attempt);
+            assert!(delay <= max && delay >= max / 2, "{:?}", delay);
+        }
+        assert!(backoff(100) <= BACKOFF_MAX);
+    }
+
+    #[test]
+    fn is_idempotent_works() {
+        assert!(is_idempotent(&Method::GET));
+        assert!(is_idempotent(&Method::PUT));
+        assert!(!is_idempotent(&Method::POST));
+        assert!(!is_idempotent(&Method::PATCH));
+    }
+
+    #[test]
+    fn parse_retry_after_works() {
+        let now = httpdate::parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT").unwrap();
+        assert_eq!(parse_retry_after("120", now), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now), Some(Duration::from_secs(30)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[tokio::test]
+    async fn long_retry_after_is_not_retried() {
+        let (url, requests) =
+            capture_server(b"HTTP/1.1 503 Service Unavailable\r\nRetry-After: 99999999\r\nContent-Length: 0\r\n\r\n")
+                .await;
+        let args = RetryArgs { retries: 3, retry_status: vec![StatusCode::SERVICE_UNAVAILABLE] };
+        let client = reqwest::Client::new();
+        let req = client.get(&url).build().unwrap();
+
+        let start = Instant::now();
+        let rebuild = || async { Err(anyhow::anyhow!("unreachable")) };
+        let resp = send(&args, req, |req| async { Ok(client.execute(req).await?) }, rebuild).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,267 @@
+use anyhow::{Context, Result};
+use clap::Clap;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, COOKIE, SET_COOKIE};
+use reqwest::{Request, Response, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::auth::Auth;
+use crate::redirect::same_origin;
+
+// session 相关的参数
+
+#[derive(Clap, Debug)]
+pub struct SessionArgs {
+    /// 使用并更新命名 session（或 session 文件路径），在多次请求间保存 cookie、请求头和认证信息
+    #[clap(long, value_name = "NAME_OR_PATH")]
+    session: Option<String>,
+    /// 使用 session，但不把这次请求的变化写回
+    #[clap(long, value_name = "NAME_OR_PATH", conflicts_with = "session")]
+    session_read_only: Option<String>,
+}
+
+/// 保存在 JSON 文件中的 session
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Session {
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    cookies: BTreeMap<String, Cookie>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    auth: Option<Auth>,
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(skip)]
+    read_only: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Cookie {
+    value: String,
+    #[serde(default = "default_cookie_path")]
+    path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires: Option<u64>,
+    #[serde(default)]
+    secure: bool,
+}
+
+fn default_cookie_path() -> String {
+    "/".into()
+}
+
+impl Session {
+    /// 读取 session 文件，文件不存在时使用空的 session
+    pub fn load(args: &SessionArgs, url: &str) -> Result<Option<Self>> {
+        let (name, read_only) = match (&args.session, &args.session_read_only) {
+            (Some(name), _) => (name, false),
+            (_, Some(name)) => (name, true),
+            _ => return Ok(None),
+        };
+
+        let path = session_path(name, &url.parse()?);
+        let mut session: Session = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("Invalid session file {}", path.display()))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Session::default(),
+            // 其它错误不能当作空的 session，否则之后保存时会覆盖原来的文件
+            Err(e) => return Err(e).with_context(|| format!("Failed to read session file {}", path.display())),
+        };
+        session.path = path;
+        session.read_only = read_only;
+
+        Ok(Some(session))
+    }
+
+    pub fn auth(&self) -> Option<&Auth> {
+        self.auth.as_ref()
+    }
+
+    /// 把 session 中的请求头和 cookie 加到请求上，命令行上的同名请求头优先
+    pub fn apply(&self, req: &mut Request) {
+        let now = now();
+        let secure = req.url().scheme() == "https";
+        let path = req.url().path().to_string();
+        let headers = req.headers_mut();
+
+        for (name, value) in self.headers.iter() {
+            if let (Ok(name), Ok(value)) = (name.parse::<HeaderName>(), HeaderValue::from_str(value)) {
+                if !headers.contains_key(&name) {
+                    headers.insert(name, value);
+                }
+            }
+        }
+
+        let cookies: Vec<_> = self
+            .cookies
+            .iter()
+            .filter(|(_, c)| c.expires.is_none_or(|t| t > now) && (!c.secure || secure) && path.starts_with(&c.path))
+            .map(|(name, c)| format!("{}={}", name, c.value))
+            .collect();
+        if !cookies.is_empty() && !headers.contains_key(COOKIE) {
+            if let Ok(value) = HeaderValue::from_str(&cookies.join("; ")) {
+                headers.insert(COOKIE, value);
+            }
+        }
+    }
+
+    /// 记录这次请求的请求头和认证信息，以及响应中的 Set-Cookie，然后写回文件。
+    /// 重定向到其它服务器时，那里设置的 cookie 不属于这个 session，不会保存
+    pub fn update(&mut self, headers: &HeaderMap, auth: Option<&Auth>, origin: &Url, resp: &Response) -> Result<()> {
+        for (name, value) in headers.iter() {
+            let name = name.as_str();
+            // 和 HTTPie 一样，不保存只对单次请求有意义的请求头
+            if name.starts_with("content-") || name.starts_with("if-") || name == "cookie" {
+                continue;
+            }
+            if let Ok(value) = value.to_str() {
+                self.headers.insert(name.to_string(), value.to_string());
+            }
+        }
+
+        if let Some(auth) = auth {
+            self.auth = Some(auth.clone());
+        }
+
+        let now = now();
+        if same_origin(origin, resp.url()) {
+            for value in resp.headers().get_all(SET_COOKIE) {
+                if let Some((name, cookie)) = value.to_str().ok().and_then(parse_set_cookie) {
+                    self.cookies.insert(name, cookie);
+                }
+            }
+        }
+        self.cookies.retain(|_, c| c.expires.is_none_or(|t| t > now));
+
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to save session {}", self.path.display()))
+    }
+}
+
+// 命名 session 保存在 <config>/sessions/<host_port>/<name>.json，包含 / 的值视为文件路径
+fn session_path(name: &str, url: &Url) -> PathBuf {
+    if name.contains(std::path::MAIN_SEPARATOR) || name.contains('/') {
+        return PathBuf::from(name);
+    }
+
+    let host = match url.port() {
+        Some(port) => format!("{}_{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+
+    config_dir().join("sessions").join(host).join(format!("{}.json", name))
+}
+
+// 配置目录：$HTTPIE_CONFIG_DIR，或者 $XDG_CONFIG_HOME/httpie，或者 ~/.config/httpie
+fn config_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("HTTPIE_CONFIG_DIR") {
+        return dir.into();
+    }
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_default();
+    base.join("httpie")
+}
+
+// 解析 Set-Cookie，只保留 session 需要的属性
+fn parse_set_cookie(s: &str) -> Option<(String, Cookie)> {
+    let mut parts = s.split(';');
+    let (name, value) = parts.next()?.split_once('=')?;
+    let mut cookie = Cookie {
+        value: value.trim().to_string(),
+        path: default_cookie_path(),
+        expires: None,
+        secure: false,
+    };
+
+    let mut max_age = None;
+    for attr in parts {
+        let (k, v) = attr.split_once('=').unwrap_or((attr, ""));
+        let v = v.trim();
+        match k.trim().to_ascii_lowercase().as_str() {
+            "path" if v.starts_with('/') => cookie.path = v.to_string(),
+            "expires" => cookie.expires = httpdate::parse_http_date(v).ok().map(unix_secs),
+            "max-age" => max_age = v.parse::<i64>().ok(),
+            "secure" => cookie.secure = true,
+            _ => {}
+        }
+    }
+
+    // Max-Age 优先于 Expires，小于等于 0 表示删除 cookie
+    if let Some(max_age) = max_age {
+        cookie.expires = Some(if max_age <= 0 { 0 } else { now() + max_age as u64 });
+    }
+
+    Some((name.trim().to_string(), cookie))
+}
+
+fn unix_secs(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO).as_secs()
+}
+
+fn now() -> u64 {
+    unix_secs(SystemTime::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_set_cookie_works() {
+        assert_eq!(
+            parse_set_cookie("sid=abc; Path=/api; HttpOnly; Secure"),
+            Some(("sid".into(), Cookie { value: "abc".into(), path: "/api".into(), expires: None, secure: true }))
+        );
+        assert_eq!(
+            parse_set_cookie("a=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT").map(|(_, c)| c.expires),
+            Some(Some(1445412480))
+        );
+        assert_eq!(parse_set_cookie("a=1; Max-Age=0").map(|(_, c)| c.expires), Some(Some(0)));
+        assert_eq!(parse_set_cookie("invalid"), None);
+    }
+
+    #[test]
+    fn session_path_works() {
+        let url = "http://localhost:8080/a".parse().unwrap();
+        assert_eq!(session_path("./s.json", &url), PathBuf::from("./s.json"));
+        assert!(session_path("user", &url).ends_with("sessions/localhost_8080/user.json"));
+        let url = "https://example.com/".parse().unwrap();
+        assert!(session_path("user", &url).ends_with("sessions/example.com/user.json"));
+    }
+
+    #[test]
+    fn load_works() {
+        let dir = env::temp_dir().join(format!("httpie-session-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let load = |name: &str| {
+            let args = SessionArgs { session: Some(dir.join(name).to_string_lossy().into_owned()), session_read_only: None };
+            Session::load(&args, "http://localhost/")
+        };
+
+        assert!(load("missing.json").unwrap().is_some());
+        fs::write(dir.join("binary.json"), [0xff, 0xfe]).unwrap();
+        assert!(load("binary.json").is_err());
+        fs::create_dir_all(dir.join("dir.json")).unwrap();
+        assert!(load("dir.json").is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,406 @@
+use anyhow::{anyhow, Result};
+use clap::ArgEnum;
+use reqwest::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use reqwest::{Method, Request};
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use crate::items::RequestItem;
+use crate::output::title_case;
+use crate::request::guess_mime;
+
+// --as 可以导出的格式
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Curl,
+    Wget,
+    RustReqwest,
+    PythonRequests,
+    Fetch,
+}
+
+/// 从构造好的请求中提取生成代码需要的信息
+#[derive(Debug, PartialEq)]
+pub struct Snippet {
+    method: Method,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Body,
+}
+
+#[derive(Debug, PartialEq)]
+enum Body {
+    None,
+    Text(String),
+    File(PathBuf),
+    Multipart(Vec<Field>),
+}
+
+#[derive(Debug, PartialEq)]
+enum Field {
+    Text(String, String),
+    File { name: String, path: PathBuf, mime: Option<String> },
+}
+
+impl Snippet {
+    /// 流式 body（@file 和 multipart）无法从请求中读出，改为根据请求项引用原来的文件。
+    /// 生成的命令和代码只能包含文本，不是 UTF-8 的 body 和请求头会返回错误
+    pub fn new(req: &Request, items: &[RequestItem]) -> Result<Self> {
+        let body = match req.body() {
+            None => Body::None,
+            Some(body) => match body.as_bytes() {
+                Some(bytes) => Body::Text(
+                    String::from_utf8(bytes.to_vec())
+                        .map_err(|_| anyhow!("Cannot export a binary request body, save it to a file and use @file"))?,
+                ),
+                None => match items.iter().find_map(|item| match item {
+                    RequestItem::Body(path) => Some(path.clone()),
+                    _ => None,
+                }) {
+                    Some(path) => Body::File(path),
+                    None => Body::Multipart(multipart_fields(items)),
+                },
+            },
+        };
+
+        // 长度和 multipart 的 boundary 由各个工具自己生成
+        let headers = req
+            .headers()
+            .iter()
+            .filter(|(name, _)| *name != CONTENT_LENGTH)
+            .filter(|(name, _)| !(*name == CONTENT_TYPE && matches!(body, Body::Multipart(_))))
+            .map(|(name, value)| {
+                let value = value.to_str().map_err(|_| anyhow!("Cannot export the non-text header {}", name))?;
+                Ok((title_case(name.as_str()), value.to_string()))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Snippet { method: req.method().clone(), url: req.url().to_string(), headers, body })
+    }
+
+    pub fn render(&self, format: Format) -> Result<String> {
+        match format {
+            Format::Curl => Ok(self.curl()),
+            Format::Wget => self.wget(),
+            Format::RustReqwest => Ok(self.rust_reqwest()),
+            Format::PythonRequests => Ok(self.python_requests()),
+            Format::Fetch => Ok(self.fetch()),
+        }
+    }
+
+    fn curl(&self) -> String {
+        let mut args = vec!["curl".to_string()];
+        match self.method {
+            Method::GET => {}
+            // -X HEAD 会让 curl 一直等待 body
+            Method::HEAD => args.push("--head".into()),
+            _ => args.push(format!("-X {}", shell_quote(self.method.as_str()))),
+        }
+        args.push(shell_quote(&self.url).into_owned());
+        for (name, value) in &self.headers {
+            args.push(format!("-H {}", shell_quote(&format!("{}: {}", name, value))));
+        }
+        match &self.body {
+            Body::None => {}
+            Body::Text(text) => args.push(format!("--data-raw {}", shell_quote(text))),
+            Body::File(path) => args.push(format!("--data-binary {}", shell_quote(&format!("@{}", path.display())))),
+            Body::Multipart(fields) => {
+                for field in fields {
+                    match field {
+                        // --form-string 不会把 @ 和 < 开头的值当作文件
+                        Field::Text(name, value) => {
+                            args.push(format!("--form-string {}", shell_quote(&format!("{}={}", name, value))))
+                        }
+                        Field::File { name, path, mime } => {
+                            let mut value = format!("{}=@{}", name, path.display());
+                            if let Some(mime) = mime {
+                                value.push_str(&format!(";type={}", mime));
+                            }
+                            args.push(format!("-F {}", shell_quote(&value)));
+                        }
+                    }
+                }
+            }
+        }
+
+        args.join(" \\\n  ")
+    }
+
+    fn wget(&self) -> Result<String> {
+        let mut args = vec!["wget".to_string(), "-O -".to_string()];
+        if self.method != Method::GET {
+            args.push(format!("--method={}", shell_quote(self.method.as_str())));
+        }
+        for (name, value) in &self.headers {
+            args.push(format!("--header={}", shell_quote(&format!("{}: {}", name, value))));
+        }
+        match &self.body {
+            Body::None => {}
+            Body::Text(text) => args.push(format!("--body-data={}", shell_quote(text))),
+            Body::File(path) => args.push(format!("--body-file={}", shell_quote(&path.display().to_string()))),
+            Body::Multipart(_) => return Err(anyhow!("wget cannot send multipart bodies, try --as=curl")),
+        }
+        args.push(shell_quote(&self.url).into_owned());
+
+        Ok(args.join(" \\\n  "))
+    }
+
+    // Rust 的 Debug 输出就是合法的字符串字面量
+    fn rust_reqwest(&self) -> String {
+        let method = match self.method {
+            Method::GET | Method::POST | Method::PUT | Method::PATCH | Method::DELETE | Method::HEAD | Method::OPTIONS => {
+                format!("reqwest::Method::{}", self.method)
+            }
+            _ => format!("reqwest::Method::from_bytes(b{:?})?", self.method.as_str()),
+        };
+
+        let mut out = String::new();
+        if let Body::Multipart(fields) = &self.body {
+            out.push_str("let form = reqwest::multipart::Form::new()");
+            for field in fields {
+                match field {
+                    Field::Text(name, value) => write!(out, "\n    .text({:?}, {:?})", name, value).unwrap(),
+                    Field::File { name, path, mime } => {
+                        let path = path.display().to_string();
+                        let file_name = file_name(&path);
+                        write!(out, "\n    .part(\n        {:?},\n        reqwest::multipart::Part::bytes(std::fs::read({:?})?)", name, path)
+                            .unwrap();
+                        write!(out, "\n            .file_name({:?})", file_name).unwrap();
+                        if let Some(mime) = mime {
+                            write!(out, "\n            .mime_str({:?})?", mime).unwrap();
+                        }
+                        out.push_str(",\n    )");
+                    }
+                }
+            }
+            out.push_str(";\n");
+        }
+
+        out.push_str("let client = reqwest::Client::new();\n");
+        write!(out, "let resp = client\n    .request({}, {:?})", method, self.url).unwrap();
+        for (name, value) in &self.headers {
+            write!(out, "\n    .header({:?}, {:?})", name, value).unwrap();
+        }
+        match &self.body {
+            Body::None => {}
+            Body::Text(text) => write!(out, "\n    .body({:?})", text).unwrap(),
+            Body::File(path) => write!(out, "\n    .body(std::fs::read({:?})?)", path.display().to_string()).unwrap(),
+            Body::Multipart(_) => out.push_str("\n    .multipart(form)"),
+        }
+        out.push_str("\n    .send()\n    .await?;\n");
+        out.push_str("println!(\"{}\", resp.text().await?);");
+
+        out
+    }
+
+    // JSON 字符串同时也是合法的 Python 和 JavaScript 字符串字面量
+    fn python_requests(&self) -> String {
+        let mut out = String::from("import requests\n\n");
+        write!(out, "resp = requests.request(\n    {},\n    {},\n", quote(self.method.as_str()), quote(&self.url)).unwrap();
+        if !self.headers.is_empty() {
+            out.push_str("    headers={\n");
+            for (name, value) in &self.headers {
+                writeln!(out, "        {}: {},", quote(name), quote(value)).unwrap();
+            }
+            out.push_str("    },\n");
+        }
+        match &self.body {
+            Body::None => {}
+            Body::Text(text) => writeln!(out, "    data={}.encode(),", quote(text)).unwrap(),
+            Body::File(path) => writeln!(out, "    data=open({}, \"rb\"),", quote(&path.display().to_string())).unwrap(),
+            Body::Multipart(fields) => {
+                let mut data = String::new();
+                let mut files = String::new();
+                for field in fields {
+                    match field {
+                        Field::Text(name, value) => writeln!(data, "        {}: {},", quote(name), quote(value)).unwrap(),
+                        Field::File { name, path, mime } => {
+                            let path = path.display().to_string();
+                            let mime = mime.as_deref().map(quote).unwrap_or_else(|| "None".into());
+                            writeln!(
+                                files,
+                                "        {}: ({}, open({}, \"rb\"), {}),",
+                                quote(name),
+                                quote(file_name(&path)),
+                                quote(&path),
+                                mime
+                            )
+                            .unwrap();
+                        }
+                    }
+                }
+                if !data.is_empty() {
+                    write!(out, "    data={{\n{}    }},\n", data).unwrap();
+                }
+                if !files.is_empty() {
+                    write!(out, "    files={{\n{}    }},\n", files).unwrap();
+                }
+            }
+        }
+        out.push_str(")\nprint(resp.text)");
+
+        out
+    }
+
+    // 面向 Node.js 18+，文件通过 fs 读取
+    fn fetch(&self) -> String {
+        let mut out = String::new();
+        if matches!(self.body, Body::File(_) | Body::Multipart(_)) {
+            out.push_str("const fs = require(\"fs\");\n\n");
+        }
+        if let Body::Multipart(fields) = &self.body {
+            out.push_str("const form = new FormData();\n");
+            for field in fields {
+                match field {
+                    Field::Text(name, value) => writeln!(out, "form.append({}, {});", quote(name), quote(value)).unwrap(),
+                    Field::File { name, path, mime } => {
+                        let path = path.display().to_string();
+                        let options = match mime {
+                            Some(mime) => format!(", {{ type: {} }}", quote(mime)),
+                            None => String::new(),
+                        };
+                        writeln!(
+                            out,
+                            "form.append({}, new Blob([fs.readFileSync({})]{}), {});",
+                            quote(name),
+                            quote(&path),
+                            options,
+                            quote(file_name(&path))
+                        )
+                        .unwrap();
+                    }
+                }
+            }
+            out.push('\n');
+        }
+
+        write!(out, "const resp = await fetch({}, {{\n  method: {},\n", quote(&self.url), quote(self.method.as_str())).unwrap();
+        if !self.headers.is_empty() {
+            out.push_str("  headers: {\n");
+            for (name, value) in &self.headers {
+                writeln!(out, "    {}: {},", quote(name), quote(value)).unwrap();
+            }
+            out.push_str("  },\n");
+        }
+        match &self.body {
+            Body::None => {}
+            Body::Text(text) => writeln!(out, "  body: {},", quote(text)).unwrap(),
+            Body::File(path) => writeln!(out, "  body: fs.readFileSync({}),", quote(&path.display().to_string())).unwrap(),
+            Body::Multipart(_) => out.push_str("  body: form,\n"),
+        }
+        out.push_str("});\nconsole.log(await resp.text());");
+
+        out
+    }
+}
+
+// 和 request::build 一样，先是文本字段，然后是文件
+fn multipart_fields(items: &[RequestItem]) -> Vec<Field> {
+    let texts = items.iter().filter_map(|item| match item {
+        RequestItem::Data(name, value) => Some(Field::Text(name.clone(), value.clone())),
+        _ => None,
+    });
+    let files = items.iter().filter_map(|item| match item {
+        RequestItem::File(file) => Some(Field::File {
+            name: file.name.clone(),
+            path: file.path.clone(),
+            mime: file.mime.clone().or_else(|| guess_mime(&file.path)).map(|m| m.to_string()),
+        }),
+        _ => None,
+    });
+
+    texts.chain(files).collect()
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+fn quote(s: &str) -> String {
+    serde_json::to_string(s).unwrap_or_default()
+}
+
+// POSIX shell 的单引号转义，只包含安全字符时不加引号
+fn shell_quote(s: &str) -> Cow<'_, str> {
+    let safe = !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c));
+    if safe {
+        Cow::Borrowed(s)
+    } else {
+        Cow::Owned(format!("'{}'", s.replace('\'', r"'\''")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snippet(items: &[RequestItem]) -> Snippet {
+        let req = reqwest::Client::new()
+            .post("http://example.com/a?q=1")
+            .header("x-note", "it's")
+            .header(CONTENT_TYPE, "application/json")
+            .body(r#"{"name":"x"}"#)
+            .build()
+            .unwrap();
+        Snippet::new(&req, items).unwrap()
+    }
+
+    #[test]
+    fn shell_quote_works() {
+        assert_eq!(shell_quote("http://a.com/b?c=1"), "'http://a.com/b?c=1'");
+        assert_eq!(shell_quote("http://a.com/b"), "http://a.com/b");
+        assert_eq!(shell_quote("it's $HOME"), r"'it'\''s $HOME'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn curl_works() {
+        assert_eq!(
+            snippet(&[]).render(Format::Curl).unwrap(),
+            "curl \\\n  -X POST \\\n  'http://example.com/a?q=1' \\\n  -H 'X-Note: it'\\''s' \\\n  \
+             -H 'Content-Type: application/json' \\\n  --data-raw '{\"name\":\"x\"}'"
+        );
+
+        let items = [
+            RequestItem::Data("a".into(), "@b".into()),
+            "f@dir/c.json;type=text/plain".parse().unwrap(),
+        ];
+        let snippet = Snippet {
+            method: Method::POST,
+            url: "http://x/".into(),
+            headers: vec![],
+            body: Body::Multipart(multipart_fields(&items)),
+        };
+        let curl = snippet.render(Format::Curl).unwrap();
+        assert!(curl.contains("--form-string a=@b"));
+        assert!(curl.contains("-F 'f=@dir/c.json;type=text/plain'"));
+        assert!(snippet.render(Format::Wget).is_err());
+    }
+
+    #[test]
+    fn binary_body_is_rejected() {
+        let req = reqwest::Client::new().post("http://example.com/").body(vec![0xff, 0xfe]).build().unwrap();
+        assert!(Snippet::new(&req, &[]).is_err());
+    }
+
+    #[test]
+    fn code_snippets_work() {
+        let snippet = snippet(&[]);
+        let rust = snippet.render(Format::RustReqwest).unwrap();
+        assert!(rust.contains(r#".request(reqwest::Method::POST, "http://example.com/a?q=1")"#));
+        assert!(rust.contains(r#".body("{\"name\":\"x\"}")"#));
+
+        let python = snippet.render(Format::PythonRequests).unwrap();
+        assert!(python.contains(r#"        "X-Note": "it's","#));
+        assert!(python.contains(r#"    data="{\"name\":\"x\"}".encode(),"#));
+
+        let fetch = snippet.render(Format::Fetch).unwrap();
+        assert!(fetch.starts_with(r#"const resp = await fetch("http://example.com/a?q=1", {"#));
+        assert!(fetch.contains(r#"  method: "POST","#));
+
+        let wget = snippet.render(Format::Wget).unwrap();
+        assert!(wget.contains("--method=POST"));
+        assert!(wget.ends_with("'http://example.com/a?q=1'"));
+    }
+}
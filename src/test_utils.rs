@@ -0,0 +1,57 @@
+// 测试用的本地 HTTP 服务器：对每个连接读取请求头和 Content-Length 指定的 body，然后原样返回预先准备的响应
+
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// 启动一个只返回固定响应的服务器，返回它的 URL
+pub async fn stub_server(response: &'static [u8]) -> String {
+    capture_server(response).await.0
+}
+
+/// 和 stub_server 一样，同时记录收到的每个请求（请求头和 body）
+pub async fn capture_server(response: &'static [u8]) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let requests = Arc::new(Mutex::new(Vec::new()));
+
+    let captured = requests.clone();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let captured = captured.clone();
+            tokio::spawn(async move {
+                let mut buf = Vec::new();
+                let mut chunk = [0; 1024];
+                let mut end = None;
+                loop {
+                    if end.is_none() {
+                        end = buf.windows(4).position(|w| w == b"\r\n\r\n").map(|i| i + 4);
+                    }
+                    if let Some(end) = end {
+                        if buf.len() >= end + content_length(&buf[..end]) {
+                            break;
+                        }
+                    }
+                    match stream.read(&mut chunk).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                    }
+                }
+                captured.lock().unwrap().push(String::from_utf8_lossy(&buf).into_owned());
+                let _ = stream.write_all(response).await;
+                let _ = stream.shutdown().await;
+            });
+        }
+    });
+
+    (format!("http://{}/", addr), requests)
+}
+
+fn content_length(head: &[u8]) -> usize {
+    String::from_utf8_lossy(head)
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse().ok())
+        .unwrap_or(0)
+}
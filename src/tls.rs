@@ -0,0 +1,417 @@
+use anyhow::{anyhow, Context, Result};
+use clap::Clap;
+use openssl::pkcs12::Pkcs12;
+use openssl::pkey::{PKey, Private};
+use openssl::stack::Stack;
+use openssl::x509::X509;
+use reqwest::{Certificate, ClientBuilder, Identity};
+use rustls::{CipherSuite, ClientConfig, ProtocolVersion, RootCertStore, ServerCertVerified, ServerCertVerifier, TLSError};
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+// TLS 相关的参数。默认使用 reqwest 的 native-tls（系统的 OpenSSL），
+// 它不能指定 TLS 1.3 和加密套件，所以指定 --ssl 或 --ciphers 时改用 rustls
+
+#[derive(Clap, Debug)]
+pub struct TlsArgs {
+    /// 是否校验服务器证书：yes、no，或者用来校验的 CA 证书文件（PEM），此时不再使用系统的根证书
+    #[clap(long, value_name = "yes|no|CA_BUNDLE", default_value = "yes")]
+    verify: Verify,
+    /// 客户端证书文件（PEM），私钥不在同一个文件中时用 --cert-key 指定
+    #[clap(long, value_name = "FILE")]
+    cert: Option<PathBuf>,
+    /// 客户端证书的私钥文件（PEM）
+    #[clap(long, value_name = "FILE", requires = "cert")]
+    cert_key: Option<PathBuf>,
+    /// 最低的 TLS 版本：tls1.2、tls1.3
+    #[clap(long, value_name = "VERSION")]
+    ssl: Option<SslVersion>,
+    /// 允许的加密套件，用冒号分隔，可以使用 OpenSSL 或 IANA 的名称，如 ECDHE-RSA-AES128-GCM-SHA256
+    #[clap(long, value_name = "CIPHERS")]
+    ciphers: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Verify {
+    Yes,
+    No,
+    Bundle(PathBuf),
+}
+
+impl FromStr for Verify {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "yes" | "true" => Verify::Yes,
+            "no" | "false" => Verify::No,
+            _ => Verify::Bundle(s.into()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SslVersion {
+    Tls12,
+    Tls13,
+}
+
+impl FromStr for SslVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "tls1.2" => Ok(SslVersion::Tls12),
+            "tls1.3" => Ok(SslVersion::Tls13),
+            _ => Err(anyhow!("Invalid TLS version {}, expected tls1.2 or tls1.3", s)),
+        }
+    }
+}
+
+// rustls 支持的加密套件和对应的 OpenSSL 名称
+const CIPHERS: [(CipherSuite, &str); 9] = [
+    (CipherSuite::TLS13_AES_128_GCM_SHA256, "TLS_AES_128_GCM_SHA256"),
+    (CipherSuite::TLS13_AES_256_GCM_SHA384, "TLS_AES_256_GCM_SHA384"),
+    (CipherSuite::TLS13_CHACHA20_POLY1305_SHA256, "TLS_CHACHA20_POLY1305_SHA256"),
+    (CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256, "ECDHE-ECDSA-AES128-GCM-SHA256"),
+    (CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384, "ECDHE-ECDSA-AES256-GCM-SHA384"),
+    (CipherSuite::TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256, "ECDHE-ECDSA-CHACHA20-POLY1305"),
+    (CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256, "ECDHE-RSA-AES128-GCM-SHA256"),
+    (CipherSuite::TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384, "ECDHE-RSA-AES256-GCM-SHA384"),
+    (CipherSuite::TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256, "ECDHE-RSA-CHACHA20-POLY1305"),
+];
+
+impl TlsArgs {
+    pub fn apply(&self, builder: ClientBuilder) -> Result<ClientBuilder> {
+        if self.ssl.is_some() || self.ciphers.is_some() {
+            return Ok(builder.use_preconfigured_tls(self.rustls_config()?));
+        }
+
+        let mut builder = match &self.verify {
+            Verify::Yes => builder,
+            Verify::No => builder.danger_accept_invalid_certs(true),
+            Verify::Bundle(path) => {
+                let mut builder = builder.tls_built_in_root_certs(false);
+                for cert in load_certs(path)? {
+                    builder = builder.add_root_certificate(Certificate::from_der(&cert.to_der()?)?);
+                }
+                builder
+            }
+        };
+
+        // native-tls 只接受 PKCS#12 格式的客户端证书，这里从 PEM 转换
+        if let Some((certs, key)) = self.identity()? {
+            let mut chain = Stack::new()?;
+            for cert in certs.iter().skip(1) {
+                chain.push(cert.clone())?;
+            }
+            let mut pkcs12 = Pkcs12::builder();
+            pkcs12.ca(chain);
+            let der = pkcs12.build("", "httpie", &key, &certs[0])?.to_der()?;
+            builder = builder.identity(Identity::from_pkcs12_der(&der, "")?);
+        }
+
+        Ok(builder)
+    }
+
+    fn rustls_config(&self) -> Result<ClientConfig> {
+        let mut config = ClientConfig::new();
+
+        match &self.verify {
+            Verify::Yes => {
+                config.root_store = match rustls_native_certs::load_native_certs() {
+                    Ok(store) | Err((Some(store), _)) => store,
+                    Err((None, e)) => return Err(anyhow!("Failed to load system root certificates: {}", e)),
+                }
+            }
+            Verify::No => config.dangerous().set_certificate_verifier(Arc::new(NoVerifier)),
+            Verify::Bundle(path) => {
+                for cert in load_certs(path)? {
+                    config
+                        .root_store
+                        .add(&rustls::Certificate(cert.to_der()?))
+                        .map_err(|e| anyhow!("Invalid CA certificate in {}: {}", path.display(), e))?;
+                }
+            }
+        }
+
+        if let Some((certs, key)) = self.identity()? {
+            let certs = certs
+                .iter()
+                .map(|cert| Ok(rustls::Certificate(cert.to_der()?)))
+                .collect::<Result<_>>()?;
+            // rustls 只认 PKCS#8 和 RSA 私钥，统一转换为 PKCS#8
+            let pem = key.private_key_to_pem_pkcs8()?;
+            let key = rustls::internal::pemfile::pkcs8_private_keys(&mut &pem[..])
+                .ok()
+                .and_then(|keys| keys.into_iter().next())
+                .ok_or_else(|| anyhow!("Failed to convert the client certificate key"))?;
+            config.set_single_client_cert(certs, key)?;
+        }
+
+        if self.ssl == Some(SslVersion::Tls13) {
+            config.versions = vec![ProtocolVersion::TLSv1_3];
+        }
+
+        if let Some(ciphers) = &self.ciphers {
+            let suites = parse_ciphers(ciphers)?;
+            config.ciphersuites = rustls::ALL_CIPHERSUITES
+                .iter()
+                .copied()
+                .filter(|s| suites.contains(&s.suite))
+                .collect();
+        }
+
+        Ok(config)
+    }
+
+    // 读取客户端证书链和私钥，私钥默认和证书在同一个文件中
+    fn identity(&self) -> Result<Option<(Vec<X509>, PKey<Private>)>> {
+        let cert = match &self.cert {
+            Some(cert) => cert,
+            None => return Ok(None),
+        };
+
+        let certs = load_certs(cert)?;
+        let key_path = self.cert_key.as_ref().unwrap_or(cert);
+        let key = fs::read(key_path).with_context(|| format!("Failed to read {}", key_path.display()))?;
+        let key = PKey::private_key_from_pem(&key)
+            .with_context(|| format!("No valid private key found in {}", key_path.display()))?;
+
+        Ok(Some((certs, key)))
+    }
+}
+
+// 读取 PEM 文件中的所有证书
+fn load_certs(path: &PathBuf) -> Result<Vec<X509>> {
+    let pem = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let certs = X509::stack_from_pem(&pem).with_context(|| format!("Invalid certificate file {}", path.display()))?;
+    if certs.is_empty() {
+        return Err(anyhow!("No certificate found in {}", path.display()));
+    }
+
+    Ok(certs)
+}
+
+fn parse_ciphers(s: &str) -> Result<Vec<CipherSuite>> {
+    s.split([':', ','])
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            CIPHERS
+                .iter()
+                .find(|(suite, openssl)| {
+                    openssl.eq_ignore_ascii_case(name) || format!("{:?}", suite).eq_ignore_ascii_case(name)
+                })
+                .map(|(suite, _)| *suite)
+                .ok_or_else(|| {
+                    let supported: Vec<_> = CIPHERS.iter().map(|(_, name)| *name).collect();
+                    anyhow!("Unsupported cipher {}, expected one of {}", name, supported.join(", "))
+                })
+        })
+        .collect()
+}
+
+// --verify=no 时接受任何服务器证书
+struct NoVerifier;
+
+impl ServerCertVerifier for NoVerifier {
+    fn verify_server_cert(
+        &self,
+        _roots: &RootCertStore,
+        _presented_certs: &[rustls::Certificate],
+        _dns_name: webpki::DNSNameRef,
+        _ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::asn1::Asn1Time;
+    use openssl::bn::{BigNum, MsbOption};
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::ssl::{SslAcceptor, SslMethod, SslVerifyMode, SslVersion as OpensslVersion};
+    use openssl::x509::extension::{BasicConstraints, SubjectAlternativeName};
+    use openssl::x509::{X509Name, X509Ref};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::path::Path;
+
+    // 签发一张 EC 证书，没有 issuer 时为自签名的 CA
+    fn issue(cn: &str, issuer: Option<(&X509Ref, &PKey<Private>)>) -> (X509, PKey<Private>) {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+
+        let mut name = X509Name::builder().unwrap();
+        name.append_entry_by_text("CN", cn).unwrap();
+        let name = name.build();
+
+        let mut serial = BigNum::new().unwrap();
+        serial.rand(64, MsbOption::MAYBE_ZERO, false).unwrap();
+
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        builder.set_serial_number(&serial.to_asn1_integer().unwrap()).unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+        match issuer {
+            Some((ca, ca_key)) => {
+                let san = SubjectAlternativeName::new().dns(cn).build(&builder.x509v3_context(Some(ca), None)).unwrap();
+                builder.append_extension(san).unwrap();
+                builder.set_issuer_name(ca.subject_name()).unwrap();
+                builder.sign(ca_key, MessageDigest::sha256()).unwrap();
+            }
+            None => {
+                builder.append_extension(BasicConstraints::new().critical().ca().build().unwrap()).unwrap();
+                builder.set_issuer_name(&name).unwrap();
+                builder.sign(&key, MessageDigest::sha256()).unwrap();
+            }
+        }
+
+        (builder.build(), key)
+    }
+
+    struct Fixture {
+        dir: PathBuf,
+        ca: X509,
+        server: (X509, PKey<Private>),
+    }
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("httpie-tls-{}-{}", name, std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+
+            let (ca, ca_key) = issue("httpie test ca", None);
+            let server = issue("localhost", Some((&ca, &ca_key)));
+            let (client, client_key) = issue("client", Some((&ca, &ca_key)));
+            fs::write(dir.join("ca.pem"), ca.to_pem().unwrap()).unwrap();
+            fs::write(dir.join("client.pem"), client.to_pem().unwrap()).unwrap();
+            fs::write(dir.join("client.key"), client_key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+
+            Fixture { dir, ca, server }
+        }
+
+        fn path(&self, name: &str) -> PathBuf {
+            self.dir.join(name)
+        }
+
+        // 启动一个 TLS 服务器，返回它的 URL
+        fn serve(&self, require_client_cert: bool, max_version: Option<OpensslVersion>) -> String {
+            let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+            acceptor.set_certificate(&self.server.0).unwrap();
+            acceptor.set_private_key(&self.server.1).unwrap();
+            acceptor.set_max_proto_version(max_version).unwrap();
+            if require_client_cert {
+                acceptor.cert_store_mut().add_cert(self.ca.clone()).unwrap();
+                acceptor.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+            }
+            let acceptor = Arc::new(acceptor.build());
+
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let port = listener.local_addr().unwrap().port();
+            std::thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let acceptor = acceptor.clone();
+                    std::thread::spawn(move || {
+                        if let Ok(mut stream) = acceptor.accept(stream) {
+                            let mut buf = [0; 1024];
+                            let _ = stream.read(&mut buf);
+                            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok");
+                            let _ = stream.shutdown();
+                        }
+                    });
+                }
+            });
+
+            format!("https://localhost:{}/", port)
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn args(verify: &str, cert: Option<&Path>, ssl: Option<&str>, ciphers: Option<&str>) -> TlsArgs {
+        TlsArgs {
+            verify: verify.parse().unwrap(),
+            cert: cert.map(|p| p.join("client.pem")),
+            cert_key: cert.map(|p| p.join("client.key")),
+            ssl: ssl.map(|s| s.parse().unwrap()),
+            ciphers: ciphers.map(String::from),
+        }
+    }
+
+    async fn get(url: &str, args: TlsArgs) -> Result<String> {
+        let client = args.apply(reqwest::Client::builder())?.build()?;
+        Ok(client.get(url).send().await?.text().await?)
+    }
+
+    #[test]
+    fn parse_works() {
+        assert_eq!("no".parse::<Verify>().ok(), Some(Verify::No));
+        assert_eq!("TRUE".parse::<Verify>().ok(), Some(Verify::Yes));
+        assert_eq!("ca.pem".parse::<Verify>().ok(), Some(Verify::Bundle("ca.pem".into())));
+        assert_eq!("tls1.3".parse::<SslVersion>().ok(), Some(SslVersion::Tls13));
+        assert!("ssl3".parse::<SslVersion>().is_err());
+
+        assert_eq!(
+            parse_ciphers("ECDHE-RSA-AES128-GCM-SHA256:TLS13_AES_256_GCM_SHA384").ok(),
+            Some(vec![CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256, CipherSuite::TLS13_AES_256_GCM_SHA384])
+        );
+        assert!(parse_ciphers("RC4-MD5").is_err());
+    }
+
+    #[tokio::test]
+    async fn verify_works() {
+        let fixture = Fixture::new("verify");
+        let url = fixture.serve(false, None);
+        let ca = fixture.path("ca.pem");
+        let ca = ca.to_str().unwrap();
+
+        assert!(get(&url, args("yes", None, None, None)).await.is_err());
+        assert_eq!(get(&url, args("no", None, None, None)).await.ok().as_deref(), Some("ok"));
+        assert_eq!(get(&url, args(ca, None, None, None)).await.ok().as_deref(), Some("ok"));
+        assert_eq!(get(&url, args(ca, None, Some("tls1.2"), None)).await.ok().as_deref(), Some("ok"));
+        assert_eq!(get(&url, args("no", None, Some("tls1.2"), None)).await.ok().as_deref(), Some("ok"));
+    }
+
+    #[tokio::test]
+    async fn client_cert_works() {
+        let fixture = Fixture::new("cert");
+        let url = fixture.serve(true, None);
+        let ca = fixture.path("ca.pem");
+        let ca = ca.to_str().unwrap();
+
+        assert!(get(&url, args(ca, None, None, None)).await.is_err());
+        assert_eq!(get(&url, args(ca, Some(&fixture.dir), None, None)).await.ok().as_deref(), Some("ok"));
+        assert_eq!(get(&url, args(ca, Some(&fixture.dir), Some("tls1.3"), None)).await.ok().as_deref(), Some("ok"));
+    }
+
+    #[tokio::test]
+    async fn min_version_and_ciphers_work() {
+        let fixture = Fixture::new("version");
+        let url = fixture.serve(false, Some(OpensslVersion::TLS1_2));
+        let ca = fixture.path("ca.pem");
+        let ca = ca.to_str().unwrap();
+
+        assert_eq!(get(&url, args(ca, None, Some("tls1.2"), None)).await.ok().as_deref(), Some("ok"));
+        assert!(get(&url, args(ca, None, Some("tls1.3"), None)).await.is_err());
+
+        let ciphers = Some("ECDHE-ECDSA-AES256-GCM-SHA384");
+        assert_eq!(get(&url, args(ca, None, None, ciphers)).await.ok().as_deref(), Some("ok"));
+        // 服务器证书是 EC 的，只有 RSA 套件时无法握手
+        assert!(get(&url, args(ca, None, None, Some("ECDHE-RSA-AES128-GCM-SHA256"))).await.is_err());
+    }
+}